@@ -0,0 +1,177 @@
+use std::{io, thread};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{rngs::StdRng, SeedableRng};
+use suumo_gen::{
+    generator::{write_run, ElementGenerator},
+    try_string_to_suumo_elements, SuumoElement, SuumoState,
+};
+
+const ELEMENTS: usize = 10_000;
+
+fn random_elements(n: usize) -> Vec<SuumoElement> {
+    let mut rng = StdRng::seed_from_u64(19);
+    (0..n)
+        .map(|_| SuumoElement::new_with_rng(&mut rng))
+        .collect()
+}
+
+fn element_generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("element_generation");
+    group.throughput(Throughput::Elements(ELEMENTS as u64));
+    group.bench_function("thread_rng", |b| {
+        b.iter(|| {
+            for _ in 0..ELEMENTS {
+                black_box(SuumoElement::new());
+            }
+        })
+    });
+    group.bench_function("std_rng", |b| {
+        let mut rng = StdRng::seed_from_u64(19);
+        b.iter(|| {
+            for _ in 0..ELEMENTS {
+                black_box(SuumoElement::new_with_rng(&mut rng));
+            }
+        })
+    });
+    group.bench_function("batched", |b| {
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        b.iter(|| {
+            for _ in 0..ELEMENTS {
+                black_box(generator.next_index());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn state_transitions(c: &mut Criterion) {
+    let elements = random_elements(ELEMENTS);
+
+    let mut group = c.benchmark_group("state_transitions");
+    group.throughput(Throughput::Elements(ELEMENTS as u64));
+    group.bench_function("next_with_suumo_element", |b| {
+        b.iter(|| {
+            let mut suumo_state = SuumoState::new();
+            for element in elements.iter() {
+                if suumo_state
+                    .next_with_suumo_element(element.clone())
+                    .is_none()
+                {
+                    suumo_state = SuumoState::new();
+                }
+            }
+            black_box(suumo_state)
+        })
+    });
+    group.bench_function("iterator", |b| {
+        b.iter(|| {
+            let mut suumo_state = SuumoState::new();
+            for _ in 0..ELEMENTS {
+                if suumo_state.next().is_none() {
+                    suumo_state = SuumoState::new();
+                }
+            }
+            black_box(suumo_state)
+        })
+    });
+    group.bench_function("run_until_terminal_nth", |b| {
+        let mut rng = StdRng::seed_from_u64(19);
+        b.iter(|| SuumoState::new().run_until_terminal_nth(&mut rng, black_box(ELEMENTS as u64)))
+    });
+    group.finish();
+}
+
+fn string_collection(c: &mut Criterion) {
+    let elements = random_elements(ELEMENTS);
+
+    let mut group = c.benchmark_group("string_collection");
+    group.throughput(Throughput::Elements(ELEMENTS as u64));
+    group.bench_function("collect_to_string_nth", |b| {
+        b.iter(|| SuumoState::new().collect_to_string_nth(black_box(ELEMENTS)))
+    });
+    group.bench_function("collect_to_string_with_suumo_elements", |b| {
+        b.iter(|| {
+            SuumoState::new().collect_to_string_with_suumo_elements(black_box(elements.clone()))
+        })
+    });
+    group.bench_function("write_run", |b| {
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        b.iter(|| {
+            let mut suumo_state = SuumoState::new();
+            write_run(
+                &mut suumo_state,
+                &mut generator,
+                &mut io::sink(),
+                Some(ELEMENTS as u64),
+            )
+            .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn parsing(c: &mut Criterion) {
+    let elements = random_elements(ELEMENTS);
+    let s: String = elements.iter().map(SuumoElement::to_string).collect();
+
+    let mut group = c.benchmark_group("parsing");
+    group.throughput(Throughput::Bytes(s.len() as u64));
+    group.bench_function("try_string_to_suumo_elements", |b| {
+        b.iter(|| try_string_to_suumo_elements(black_box(s.clone())).unwrap())
+    });
+    group.bench_function("try_from_string", |b| {
+        let strings: Vec<String> = elements.iter().map(SuumoElement::to_string).collect();
+        b.iter(|| {
+            for s in strings.iter() {
+                black_box(SuumoElement::try_from(s.clone()).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn multi_thread_scaling(c: &mut Criterion) {
+    let max_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let thread_counts = (0..)
+        .map(|i| 1 << i)
+        .take_while(|&n| n < max_threads)
+        .chain([max_threads]);
+
+    let mut group = c.benchmark_group("multi_thread_scaling");
+    for threads in thread_counts {
+        group.throughput(Throughput::Elements((ELEMENTS * threads) as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    thread::scope(|scope| {
+                        for _ in 0..threads {
+                            scope.spawn(|| {
+                                let mut suumo_state = SuumoState::new();
+                                for _ in 0..ELEMENTS {
+                                    if suumo_state.next().is_none() {
+                                        suumo_state = SuumoState::new();
+                                    }
+                                }
+                                black_box(suumo_state)
+                            });
+                        }
+                    })
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    element_generation,
+    state_transitions,
+    string_collection,
+    parsing,
+    multi_thread_scaling
+);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+#[cfg(feature = "ffi")]
+use std::{env, path::PathBuf};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+// Writes the C header for `src/ffi.rs` to `$OUT_DIR/suumo_gen.h`. The copy in
+// `include/` is committed, and `tests/ffi.rs` checks that it is up to date.
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set");
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").expect("OUT_DIR is not set"));
+    let config = cbindgen::Config {
+        usize_is_size_t: true,
+        ..Default::default()
+    };
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{}/src/ffi.rs", crate_dir))
+        .with_language(cbindgen::Language::C)
+        .with_include_guard("SUUMO_GEN_H")
+        .with_autogen_warning("/* Generated by cbindgen from src/ffi.rs. Do not edit. */")
+        .generate()
+        .expect("Failed to generate the C header")
+        .write_to_file(out_dir.join("suumo_gen.h"));
+}
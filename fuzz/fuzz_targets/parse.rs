@@ -0,0 +1,30 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use suumo_gen::{try_string_to_suumo_elements, SuumoElement, SuumoElementParser};
+
+fuzz_target!(|s: String| {
+    let result = try_string_to_suumo_elements(s.clone());
+
+    if let Ok(elements) = &result {
+        let rendered: String = elements.iter().map(SuumoElement::to_string).collect();
+        assert_eq!(rendered, s);
+    }
+
+    let mut parser = SuumoElementParser::new();
+    let mut elements = Vec::new();
+    let mut failed = false;
+    for c in s.chars() {
+        match parser.push(c) {
+            Ok(Some(element)) => elements.push(element),
+            Ok(None) => {}
+            Err(_) => failed = true,
+        }
+    }
+    failed |= parser.finish().is_err();
+    if !failed {
+        assert_eq!(result, Ok(elements));
+    } else {
+        assert!(result.is_err());
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use suumo_gen::SuumoElement;
+
+fuzz_target!(|s: String| {
+    if let Ok(element) = SuumoElement::try_from(s.clone()) {
+        assert_eq!(element.to_string(), s);
+        assert_eq!(String::from(element.clone()), s);
+        assert!(SuumoElement::ALL.contains(&element));
+    }
+});
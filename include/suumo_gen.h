@@ -0,0 +1,97 @@
+#ifndef SUUMO_GEN_H
+#define SUUMO_GEN_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Number of elements. Element indices are `0..SUUMO_ELEMENT_COUNT`.
+ */
+#define SUUMO_ELEMENT_COUNT 7
+
+/**
+ * Index of the state reached once the whole jingle has been fed.
+ */
+#define SUUMO_TERMINAL_STATE 19
+
+/**
+ * Opaque state machine handle.
+ */
+typedef struct SuumoGenState SuumoGenState;
+
+/**
+ * Creates a state machine in the initial state. Free it with
+ * `suumo_state_free`.
+ */
+struct SuumoGenState *suumo_state_new(void);
+
+/**
+ * Frees a state machine. Does nothing for `NULL`.
+ *
+ * # Safety
+ *
+ * `state` must be `NULL` or a pointer returned by `suumo_state_new` that has
+ * not been freed yet.
+ */
+void suumo_state_free(struct SuumoGenState *state);
+
+/**
+ * Feeds the element with index `element` and returns the index of the new
+ * state, or -1 if `state` is `NULL` or `element` is out of range. The
+ * terminal state only transitions to itself.
+ *
+ * # Safety
+ *
+ * `state` must be `NULL` or a valid pointer returned by `suumo_state_new`.
+ */
+int32_t suumo_state_feed(struct SuumoGenState *state, uint32_t element);
+
+/**
+ * Returns the index of the current state, or -1 if `state` is `NULL`.
+ *
+ * # Safety
+ *
+ * `state` must be `NULL` or a valid pointer returned by `suumo_state_new`.
+ */
+int32_t suumo_state_index(const struct SuumoGenState *state);
+
+/**
+ * Puts the state machine back into the initial state.
+ *
+ * # Safety
+ *
+ * `state` must be `NULL` or a valid pointer returned by `suumo_state_new`.
+ */
+void suumo_state_reset(struct SuumoGenState *state);
+
+/**
+ * Writes the UTF-8 text of the element with index `element` and a
+ * terminating NUL into `buf`, truncating it to fit `len` bytes like
+ * `snprintf`. Returns the length of the full text without the NUL, or -1 if
+ * `element` is out of range.
+ *
+ * # Safety
+ *
+ * `buf` must be `NULL` with `len` 0, or point to at least `len` writable
+ * bytes.
+ */
+ptrdiff_t suumo_element_render(uint32_t element, char *buf, size_t len);
+
+/**
+ * Parses the NUL-terminated UTF-8 string `s` into element indices, writes
+ * up to `len` of them into `out` and returns how many elements `s` holds, or
+ * -1 if `s` is `NULL` or not a sequence of elements.
+ *
+ * # Safety
+ *
+ * `s` must be `NULL` or a NUL-terminated string, and `out` must be `NULL`
+ * with `len` 0 or point to at least `len` writable bytes.
+ */
+ptrdiff_t suumo_parse(const char *s, uint8_t *out, size_t len);
+
+#endif  /* SUUMO_GEN_H */
@@ -0,0 +1,204 @@
+use std::io::Read;
+
+use crate::{
+    stats::{chi_squared_p_value, chi_squared_uniform},
+    transcript::read_elements,
+    SuumoElement, SuumoState,
+};
+
+const ELEMENTS: usize = SuumoElement::ALL.len();
+const STATES: usize = SuumoState::ALL.len();
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub total: u64,
+    pub counts: [u64; ELEMENTS],
+    pub bigrams: [[u64; ELEMENTS]; ELEMENTS],
+    pub trigrams: [[[u64; ELEMENTS]; ELEMENTS]; ELEMENTS],
+    // `prefix_counts[n]` is how many times the first `n` elements of the
+    // jingle were matched, i.e. how many times `SuumoState::ALL[n]` was entered.
+    pub prefix_counts: [u64; STATES],
+    pub longest_prefix: usize,
+    pub longest_prefix_end: u64,
+    pub completed: u64,
+    previous: [Option<usize>; 2],
+    state: SuumoState,
+}
+impl Analysis {
+    pub fn new() -> Self {
+        Self {
+            total: 0,
+            counts: [0; ELEMENTS],
+            bigrams: [[0; ELEMENTS]; ELEMENTS],
+            trigrams: [[[0; ELEMENTS]; ELEMENTS]; ELEMENTS],
+            prefix_counts: [0; STATES],
+            longest_prefix: 0,
+            longest_prefix_end: 0,
+            completed: 0,
+            previous: [None; 2],
+            state: SuumoState::new(),
+        }
+    }
+
+    pub fn from_reader(reader: impl Read) -> Result<Self, String> {
+        let mut analysis = Self::new();
+        read_elements(reader, |element| {
+            analysis.push(&element);
+            Ok(())
+        })?;
+
+        Ok(analysis)
+    }
+
+    pub fn push(&mut self, element: &SuumoElement) {
+        let i = element.index();
+        self.total += 1;
+        self.counts[i] += 1;
+        match self.previous {
+            [Some(a), Some(b)] => {
+                self.bigrams[b][i] += 1;
+                self.trigrams[a][b][i] += 1;
+            }
+            [None, Some(b)] => self.bigrams[b][i] += 1,
+            _ => {}
+        }
+        self.previous = [self.previous[1], Some(i)];
+
+        // A transcript may hold several runs back to back; start the next
+        // one once the jingle has been reached.
+        if self.state == SuumoState::S19 {
+            self.state = SuumoState::new();
+        }
+        let depth = self
+            .state
+            .next_with_suumo_element(element.clone())
+            .unwrap()
+            .index();
+        self.prefix_counts[depth] += 1;
+        if depth > self.longest_prefix {
+            self.longest_prefix = depth;
+            self.longest_prefix_end = self.total;
+        }
+        if self.state == SuumoState::S19 {
+            self.completed += 1;
+        }
+    }
+
+    pub fn chi_squared(&self) -> (f64, f64) {
+        let statistic = chi_squared_uniform(&self.counts);
+
+        (statistic, chi_squared_p_value(statistic, ELEMENTS - 1))
+    }
+
+    pub fn bigram_chi_squared(&self) -> (f64, f64) {
+        let observed: Vec<u64> = self.bigrams.iter().flatten().copied().collect();
+        let statistic = chi_squared_uniform(&observed);
+
+        (
+            statistic,
+            chi_squared_p_value(statistic, observed.len() - 1),
+        )
+    }
+
+    pub fn trigram_chi_squared(&self) -> (f64, f64) {
+        let observed: Vec<u64> = self.trigrams.iter().flatten().flatten().copied().collect();
+        let statistic = chi_squared_uniform(&observed);
+
+        (
+            statistic,
+            chi_squared_p_value(statistic, observed.len() - 1),
+        )
+    }
+}
+impl Default for Analysis {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JINGLE: &str = "あ❗️ スーモ❗️🌚ダン💥ダン💥ダン💥シャーン🎶スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚ス〜〜〜モ⤴🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝ス〜〜〜モ⤵🌞";
+
+    #[test]
+    fn analyze_transcript() {
+        let transcript = format!("ダン💥あ❗️ スーモ❗️🌚ダン💥ダン💥スモ🌝\n{}\n", JINGLE);
+        let analysis = Analysis::from_reader(transcript.as_bytes()).unwrap();
+
+        assert_eq!(analysis.total, 24);
+        assert_eq!(analysis.counts, [2, 6, 1, 7, 6, 1, 1]);
+        assert_eq!(analysis.bigrams[1][1], 3);
+        assert_eq!(analysis.bigrams[4][3], 5);
+        assert_eq!(analysis.bigrams.iter().flatten().sum::<u64>(), 23);
+        assert_eq!(analysis.trigrams[1][1][1], 1);
+        assert_eq!(
+            analysis.trigrams.iter().flatten().flatten().sum::<u64>(),
+            22
+        );
+        assert_eq!(analysis.prefix_counts[0], 2);
+        assert_eq!(analysis.prefix_counts[1], 2);
+        assert_eq!(analysis.prefix_counts[3], 2);
+        assert_eq!(analysis.prefix_counts[4], 1);
+        assert_eq!(analysis.longest_prefix, 19);
+        assert_eq!(analysis.longest_prefix_end, 24);
+        assert_eq!(analysis.completed, 1);
+    }
+
+    #[test]
+    fn analyze_back_to_back_runs() {
+        let transcript = format!("{}{}", JINGLE, JINGLE);
+        let analysis = Analysis::from_reader(transcript.as_bytes()).unwrap();
+
+        assert_eq!(analysis.total, 38);
+        assert_eq!(analysis.completed, 2);
+        assert_eq!(analysis.longest_prefix_end, 19);
+    }
+
+    #[test]
+    fn analyze_split_reads() {
+        struct OneByte<'a>(&'a [u8]);
+        impl Read for OneByte<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0.is_empty() || buf.is_empty() {
+                    return Ok(0);
+                }
+                buf[0] = self.0[0];
+                self.0 = &self.0[1..];
+                Ok(1)
+            }
+        }
+
+        let analysis = Analysis::from_reader(OneByte(JINGLE.as_bytes())).unwrap();
+        assert_eq!(analysis, Analysis::from_reader(JINGLE.as_bytes()).unwrap());
+        assert_eq!(analysis.total, 19);
+    }
+
+    #[test]
+    fn analyze_invalid_transcript() {
+        assert_eq!(
+            Analysis::from_reader("ダン💥シャーン".as_bytes()),
+            Err("Invalid string: シャーン (after 1 elements)".to_string())
+        );
+        assert_eq!(
+            Analysis::from_reader("ダン💥x".as_bytes()),
+            Err("Invalid string: x (after 1 elements)".to_string())
+        );
+        assert!(Analysis::from_reader(&b"\xff"[..]).is_err());
+        assert!(Analysis::from_reader(&"ダ".as_bytes()[..2]).is_err());
+    }
+
+    #[test]
+    fn uniform_chi_squared() {
+        let mut analysis = Analysis::new();
+        for _ in 0..10 {
+            for element in SuumoElement::ALL.iter() {
+                analysis.push(element);
+            }
+        }
+
+        assert_eq!(analysis.chi_squared(), (0.0, 1.0));
+        assert!(analysis.bigram_chi_squared().1 < 1e-6);
+    }
+}
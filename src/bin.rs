@@ -1,61 +1,935 @@
-use clap::Parser;
-use suumo_gen::SuumoState;
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
+use std::{
+    fmt::{self, Display},
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process::{self, ExitCode},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Instant,
+};
 
-#[derive(Debug, Parser)]
-#[command(name = "suumo_gen", version, about, author)]
-struct Args {
-    #[arg(short, long)]
-    limit: Option<usize>,
+use clap::{CommandFactory, FromArgMatches};
+use clap_complete::Shell;
+use cli::{check_run_args, Args, Command, Format, GlobalArgs, RunArgs};
+use config::Config;
+use num_bigint::BigUint;
+use num_rational::Ratio;
+use num_traits::ToPrimitive;
+use rand::rngs::StdRng;
+use serde_json::json;
+#[cfg(feature = "metrics")]
+use suumo_gen::metrics::{self, Metrics, MetricsObserver};
+use suumo_gen::{
+    analyze::Analysis,
+    diagram,
+    generator::{count_run_until, seeded_rng, write_run_observed_until, ElementGenerator},
+    history::SuumoHistory,
+    observer::{run_observed_until, TransitionObserver},
+    output::{Compression, RotatingWriter},
+    pattern::{Pattern, PatternState},
+    stats,
+    summary::Summarizer,
+    transcript::{self, TranscriptDecoder, TranscriptEncoder},
+    SuumoElement, SuumoState,
+};
+
+mod cli;
+mod config;
+
+// Exit codes, also listed in the help text. The jingle being generated is a
+// success.
+const EXIT_ERROR: u8 = 1;
+const EXIT_LIMIT_HIT: u8 = 2;
+const EXIT_INTERRUPTED: u8 = 130;
+
+// Set by Ctrl-C or `--timeout`. The generators check it between elements, so
+// a stopped run still ends with whole elements and flushed output.
+static STOP: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+fn main() -> ExitCode {
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(EXIT_ERROR.into());
+    });
+    let (args, matches) = config
+        .apply(Args::command())
+        .try_get_matches()
+        .and_then(|matches| {
+            check_run_args(&matches)?;
+            config.check(&matches)?;
+            Ok((Args::from_arg_matches(&matches)?, matches))
+        })
+        .unwrap_or_else(|e| {
+            // clap exits with 2 on usage errors, which would look like a run
+            // that hit its limit.
+            let _ = e.print();
+            process::exit(if e.use_stderr() { EXIT_ERROR.into() } else { 0 });
+        });
+    let command = args.command.unwrap_or(Command::Run(args.run));
+    let global = args.global.for_command(&command, &matches);
+
+    let done = |result: Result<(), String>| result.map(|_| ExitCode::SUCCESS);
+    let result = match command {
+        Command::Run(run_args) => run(&run_args, &global),
+        Command::Parse { input } => done(parse(input, &global)),
+        Command::Verify { input } => verify(input, &global),
+        Command::Stats => done(expected_stats(&global)),
+        Command::Diagram => done(state_diagram(&global)),
+        Command::Cdf { steps, exact } => done(cdf(steps, exact, &global)),
+        Command::Analyze { file } => done(analyze(file, &global)),
+        Command::Summarize { file, depth } => done(summarize(file, depth as usize, &global)),
+        Command::Convert { input } => done(convert(input, &global)),
+        Command::Completions { shell } => done(completions(shell, &global)),
+        Command::Man { dir } => done(man_pages(&dir)),
+    };
+
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        ExitCode::from(EXIT_ERROR)
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Terminated,
+    LimitHit,
+    TimedOut,
+    Interrupted,
+}
+impl Outcome {
+    fn exit_code(self) -> ExitCode {
+        match self {
+            Self::Terminated => ExitCode::SUCCESS,
+            Self::LimitHit | Self::TimedOut => ExitCode::from(EXIT_LIMIT_HIT),
+            Self::Interrupted => ExitCode::from(EXIT_INTERRUPTED),
+        }
+    }
+}
+impl Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Terminated => "terminated",
+            Self::LimitHit => "limit hit",
+            Self::TimedOut => "timeout",
+            Self::Interrupted => "interrupted",
+        })
+    }
+}
+
+// Where a run stopped. `state` indexes `SuumoState::ALL`, or the states of
+// the pattern when a pattern file is used, in which case there is no
+// `deepest` state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RunSummary {
+    steps: u64,
+    state: usize,
+    deepest: Option<usize>,
+    terminated: bool,
+}
+impl RunSummary {
+    fn outcome(&self) -> Outcome {
+        if self.terminated {
+            Outcome::Terminated
+        } else if INTERRUPTED.load(Ordering::Relaxed) {
+            Outcome::Interrupted
+        } else if STOP.load(Ordering::Relaxed) {
+            Outcome::TimedOut
+        } else {
+            Outcome::LimitHit
+        }
+    }
+}
+impl Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.terminated {
+            write!(f, "reached S{} after {} elements", self.state, self.steps)?;
+        } else {
+            write!(
+                f,
+                "stopped in S{} after {} elements",
+                self.state, self.steps
+            )?;
+        }
+        match self.deepest {
+            Some(deepest) => write!(f, ", deepest S{}", deepest),
+            None => Ok(()),
+        }
+    }
+}
+
+fn run(args: &RunArgs, global: &GlobalArgs) -> Result<ExitCode, String> {
+    match global.format {
+        Format::Json => return Err("run does not support --format json".to_string()),
+        Format::Binary if args.pattern_file.is_some() || args.is_count_only() => {
+            return Err(
+                "--format binary cannot be used with --pattern-file, --count-only or \
+                 --rendering count"
+                    .to_string(),
+            )
+        }
+        _ => {}
+    }
+    if global.output.is_none() && (args.rotate_size.is_some() || args.compress != Compression::None)
+    {
+        return Err("--rotate-size and --compress require --output".to_string());
+    }
+
+    let pattern = args
+        .pattern_file
+        .as_ref()
+        .map(Pattern::from_file)
+        .transpose()?;
+
+    let options = RunOptions::from_args(args, global);
+    if options.count_only && (options.trace.is_some() || options.progress || options.repeats()) {
+        return Err(
+            "--trace, --progress and --metrics-addr cannot be used with --rendering count"
+                .to_string(),
+        );
+    }
+    if options.repeats() && global.format == Format::Binary {
+        return Err(
+            "--metrics-addr cannot be used with --format binary, as a transcript holds one run"
+                .to_string(),
+        );
+    }
+    #[cfg(feature = "metrics")]
+    if let (Some(addr), Some(metrics)) = (&args.metrics_addr, &options.metrics) {
+        let addr = metrics::serve(addr.as_str(), metrics.clone())
+            .map_err(|e| format!("Failed to serve metrics on {}: {}", addr, e))?;
+        eprintln!("serving metrics at http://{}/metrics", addr);
+    }
+
+    let start = Instant::now();
+    #[cfg(not(target_arch = "wasm32"))]
+    ctrlc::set_handler(|| {
+        // A second Ctrl-C quits without waiting for the output.
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            process::exit(EXIT_INTERRUPTED.into());
+        }
+        STOP.store(true, Ordering::Relaxed);
+    })
+    .map_err(|e| format!("Failed to handle Ctrl-C: {}", e))?;
+    if let Some(timeout) = args.timeout {
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            STOP.store(true, Ordering::Relaxed);
+        });
+    }
+
+    let summary = gen(options, pattern)?;
+    let outcome = summary.outcome();
+    eprintln!("{}: {} ({:.2?})", outcome, summary, start.elapsed());
+
+    Ok(outcome.exit_code())
+}
+
+#[derive(Debug, Clone)]
+struct Output {
+    path: PathBuf,
+    rotate_size: Option<u64>,
+    compression: Compression,
+}
 
+#[derive(Debug, Clone)]
+struct RunOptions {
+    limit: Option<usize>,
+    timeout: bool,
+    count_only: bool,
+    trace: Option<usize>,
+    progress: bool,
+    seed: Option<u64>,
+    format: Format,
+    output: Option<Output>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<Metrics>>,
     #[cfg(feature = "multi-thread")]
-    #[arg(short, long)]
-    therads: Option<usize>,
+    threads: Option<usize>,
 }
+impl RunOptions {
+    fn from_args(args: &RunArgs, global: &GlobalArgs) -> Self {
+        Self {
+            limit: args.limit,
+            timeout: args.timeout.is_some(),
+            count_only: args.is_count_only(),
+            trace: args.trace,
+            progress: args.progress,
+            seed: global.seed,
+            format: global.format,
+            output: global.output.as_ref().map(|path| Output {
+                path: path.clone(),
+                rotate_size: args.rotate_size,
+                compression: args.compress,
+            }),
+            #[cfg(feature = "metrics")]
+            metrics: args.metrics_addr.as_ref().map(|_| Arc::new(Metrics::new())),
+            #[cfg(feature = "multi-thread")]
+            threads: args.threads,
+        }
+    }
 
-fn main() {
-    let args = Args::parse();
+    // Whether the output may stop before the jingle, in which case it ends
+    // with a line break.
+    fn is_limited(&self) -> bool {
+        self.limit.is_some() || self.timeout
+    }
 
-    gen(args);
+    // Whether a new run starts whenever one reaches the jingle, which keeps
+    // the metrics endpoint up until the generator is stopped.
+    fn repeats(&self) -> bool {
+        #[cfg(feature = "metrics")]
+        if self.metrics.is_some() {
+            return true;
+        }
+
+        false
+    }
 }
 
 #[cfg(not(feature = "multi-thread"))]
-fn gen(args: Args) {
-    main_loop(args.limit);
+fn gen(options: RunOptions, pattern: Option<Pattern>) -> Result<RunSummary, String> {
+    main_loop(&options, pattern.as_ref())
 }
 
 #[cfg(feature = "multi-thread")]
-fn gen(args: Args) {
-    use std::{sync::mpsc, thread};
+fn gen(options: RunOptions, pattern: Option<Pattern>) -> Result<RunSummary, String> {
+    use std::sync::mpsc;
     use sysinfo::System;
 
     let mut sys = System::new_all();
     sys.refresh_all();
 
-    let threads = if let Some(threads) = args.therads {
+    let threads = if let Some(threads) = options.threads {
         threads
     } else {
         sys.cpus().len()
     };
+    if (options.output.is_some() || options.format == Format::Binary) && threads > 1 {
+        return Err(
+            "--output and --format binary can only be used with a single thread (-t 1)".to_string(),
+        );
+    }
     let (tx, rx) = mpsc::channel();
-    for _ in 0..threads {
+    for i in 0..threads {
         let tx = tx.clone();
+        let pattern = pattern.clone();
+        // Every thread gets its own seed so that they don't all generate the
+        // same run.
+        let options = RunOptions {
+            seed: options.seed.map(|seed| seed.wrapping_add(i as u64)),
+            ..options.clone()
+        };
         thread::spawn(move || {
-            main_loop(args.limit);
-            let _ = tx.send(());
+            let _ = tx.send(main_loop(&options, pattern.as_ref()));
         });
     }
 
-    let _ = rx.recv();
+    rx.recv()
+        .unwrap_or_else(|_| Err("Every generator thread stopped without a result".to_string()))
+}
+
+fn main_loop(options: &RunOptions, pattern: Option<&Pattern>) -> Result<RunSummary, String> {
+    let mut rng = seeded_rng(options.seed);
+    let result = match &options.output {
+        Some(output) => RotatingWriter::new(&output.path, output.rotate_size, output.compression)
+            .and_then(|mut writer| {
+                let summary = write_output(&mut writer, options, pattern, &mut rng)?;
+                writer.finish().map(|_| summary)
+            }),
+        None => {
+            let mut writer = BufWriter::with_capacity(1 << 20, io::stdout());
+            write_output(&mut writer, options, pattern, &mut rng)
+                .and_then(|summary| writer.flush().map(|_| summary))
+        }
+    };
+
+    result.map_err(|e| e.to_string())
+}
+
+fn write_output(
+    writer: &mut impl Write,
+    options: &RunOptions,
+    pattern: Option<&Pattern>,
+    rng: &mut StdRng,
+) -> io::Result<RunSummary> {
+    if options.count_only {
+        let summary = match pattern {
+            Some(pattern) => {
+                let limit = options.limit.map_or(u64::MAX, |limit| limit as u64);
+                let mut state = PatternState::new(pattern);
+                let mut steps = 0;
+                while steps < limit
+                    && !STOP.load(Ordering::Relaxed)
+                    && state.next_with_rng(rng).is_some()
+                {
+                    steps += 1;
+                }
+                RunSummary {
+                    steps,
+                    state: state.state(),
+                    deepest: None,
+                    terminated: state.is_terminal(),
+                }
+            }
+            None => {
+                let mut state = SuumoState::new();
+                let progress = count_run_until(
+                    &mut state,
+                    &mut ElementGenerator::new(rng),
+                    options.limit.map(|limit| limit as u64),
+                    &STOP,
+                );
+                RunSummary {
+                    steps: progress.steps,
+                    state: state.index(),
+                    deepest: Some(progress.deepest),
+                    terminated: state == SuumoState::S19,
+                }
+            }
+        };
+        writeln!(writer, "{}", summary.steps)?;
+        return Ok(summary);
+    }
+
+    match pattern {
+        Some(pattern) => pattern_loop(writer, options, pattern, rng),
+        None if options.format == Format::Binary => {
+            let mut encoder = TranscriptEncoder::new(&mut *writer, options.seed)?;
+            let (summary, history) = observed_loop(&mut encoder, options, rng)?;
+            encoder.finish()?;
+            print_trace(history);
+            Ok(summary)
+        }
+        None => suumo_loop(writer, options, rng),
+    }
+}
+
+fn suumo_loop(
+    writer: &mut impl Write,
+    options: &RunOptions,
+    rng: &mut StdRng,
+) -> io::Result<RunSummary> {
+    let mut suumo_state = SuumoState::new();
+    let mut generator = ElementGenerator::new(rng);
+    let mut observers = RunObservers::new(options);
+    let mut limit = options.limit.map(|limit| limit as u64);
+    let mut steps = 0;
+    let mut deepest = 0;
+
+    // Printing is not a `WriteObserver`: writing every element through
+    // `on_step` takes about 2.5 times as long as the batched copies of
+    // `write_run_observed_until`, which the other observers ride along with.
+    let mut list = observers.list();
+    loop {
+        // An empty list still costs a call per element, so the common case
+        // gets `()`, which compiles down to the plain `write_run`.
+        let progress = if list.is_empty() {
+            write_run_observed_until(
+                &mut suumo_state,
+                &mut generator,
+                writer,
+                limit,
+                &mut (),
+                &STOP,
+            )
+        } else {
+            write_run_observed_until(
+                &mut suumo_state,
+                &mut generator,
+                writer,
+                limit,
+                &mut list,
+                &STOP,
+            )
+        }?;
+        steps += progress.steps;
+        deepest = deepest.max(progress.deepest);
+        limit = limit.map(|limit| limit - progress.steps);
+
+        if !options.repeats()
+            || suumo_state != SuumoState::S19
+            || limit == Some(0)
+            || STOP.load(Ordering::Relaxed)
+        {
+            break;
+        }
+        suumo_state = SuumoState::new();
+    }
+    drop(list);
+    if options.is_limited() {
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    print_trace(observers.history);
+
+    Ok(RunSummary {
+        steps,
+        state: suumo_state.index(),
+        deepest: Some(deepest),
+        terminated: suumo_state == SuumoState::S19,
+    })
+}
+
+// Reports the best partial match so far to stderr.
+struct ProgressObserver;
+impl TransitionObserver for ProgressObserver {
+    fn on_new_max_depth(&mut self, step: u64, depth: usize) -> io::Result<()> {
+        eprintln!("step {}: reached S{}", step, depth);
+        Ok(())
+    }
+}
+
+// The observers enabled by the options: the history for --trace, --progress
+// and the metrics.
+struct RunObservers {
+    history: Option<SuumoHistory>,
+    progress: Option<ProgressObserver>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<MetricsObserver>,
 }
+impl RunObservers {
+    fn new(options: &RunOptions) -> Self {
+        Self {
+            history: options.trace.map(SuumoHistory::new),
+            progress: options.progress.then_some(ProgressObserver),
+            #[cfg(feature = "metrics")]
+            metrics: options.metrics.clone().map(MetricsObserver::new),
+        }
+    }
 
-fn main_loop(limit: Option<usize>) {
+    fn list(&mut self) -> Vec<&mut dyn TransitionObserver> {
+        let mut observers: Vec<&mut dyn TransitionObserver> = Vec::new();
+        if let Some(history) = self.history.as_mut() {
+            observers.push(history);
+        }
+        if let Some(progress) = self.progress.as_mut() {
+            observers.push(progress);
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = self.metrics.as_mut() {
+            observers.push(metrics);
+        }
+        observers
+    }
+}
+
+// Slower than `suumo_loop`, as every element goes through `sink` one by one.
+// The history is returned so that it can be printed once the output has been
+// flushed.
+fn observed_loop(
+    sink: &mut dyn TransitionObserver,
+    options: &RunOptions,
+    rng: &mut StdRng,
+) -> io::Result<(RunSummary, Option<SuumoHistory>)> {
     let mut suumo_state = SuumoState::new();
+    let mut generator = ElementGenerator::new(rng);
+    let mut observers = RunObservers::new(options);
+
+    let mut list = observers.list();
+    list.insert(0, sink);
+    let progress = run_observed_until(
+        &mut suumo_state,
+        &mut generator,
+        options.limit.map(|limit| limit as u64),
+        &mut list,
+        &STOP,
+    )?;
+    drop(list);
+    let history = observers.history;
+
+    let summary = RunSummary {
+        steps: progress.steps,
+        state: suumo_state.index(),
+        deepest: Some(progress.deepest),
+        terminated: suumo_state == SuumoState::S19,
+    };
+    Ok((summary, history))
+}
+
+fn print_trace(history: Option<SuumoHistory>) {
+    let Some(history) = history else {
+        return;
+    };
+
+    eprintln!(
+        "ended in {:?} after {} elements",
+        history.state(),
+        history.steps()
+    );
+    if let Some(reset) = history.last_reset() {
+        eprintln!("last reset: {}", reset);
+    }
+    eprintln!("last {} transitions:", history.transitions().count());
+    for transition in history.transitions() {
+        eprintln!("  {}", transition);
+    }
+}
+
+fn pattern_loop(
+    writer: &mut impl Write,
+    options: &RunOptions,
+    pattern: &Pattern,
+    rng: &mut StdRng,
+) -> io::Result<RunSummary> {
+    let mut pattern_state = PatternState::new(pattern);
+    let mut steps = 0;
+
+    while options.limit.is_none_or(|limit| steps < limit as u64) && !STOP.load(Ordering::Relaxed) {
+        let Some((_, element)) = pattern_state.next_with_rng(rng) else {
+            break;
+        };
+        writer.write_all(pattern.elements()[element].text.as_bytes())?;
+        steps += 1;
+    }
+    if options.is_limited() {
+        writeln!(writer)?;
+    }
+
+    Ok(RunSummary {
+        steps,
+        state: pattern_state.state(),
+        deepest: None,
+        terminated: pattern_state.is_terminal(),
+    })
+}
+
+fn open_input(path: Option<PathBuf>) -> Result<Box<dyn Read>, String> {
+    match path {
+        Some(path) => File::open(&path)
+            .map(|file| Box::new(file) as Box<dyn Read>)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e)),
+        None => Ok(Box::new(io::stdin().lock())),
+    }
+}
+
+fn create_output(path: Option<&Path>) -> Result<Box<dyn Write>, String> {
+    let writer: Box<dyn Write> = match path {
+        Some(path) => Box::new(
+            File::create(path)
+                .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?,
+        ),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    Ok(Box::new(BufWriter::with_capacity(1 << 20, writer)))
+}
+
+fn write_error(e: io::Error) -> String {
+    format!("Failed to write output: {}", e)
+}
+
+fn text_only(global: &GlobalArgs, command: &str) -> Result<(), String> {
+    if global.format != Format::Text {
+        return Err(format!("{} only supports --format text", command));
+    }
+
+    Ok(())
+}
+
+fn parse(input: Option<PathBuf>, global: &GlobalArgs) -> Result<(), String> {
+    let reader = open_input(input)?;
+    let mut writer = create_output(global.output.as_deref())?;
+    let mut state = SuumoState::new();
+    let mut step = 0u64;
+
+    match global.format {
+        Format::Text => {
+            transcript::read_elements(reader, |element| {
+                state.next_with_suumo_element(element.clone());
+                step += 1;
+                writeln!(writer, "{}\t{:?}\t{:?}", step, element, state).map_err(write_error)
+            })?;
+        }
+        Format::Json => {
+            writer.write_all(b"[").map_err(write_error)?;
+            transcript::read_elements(reader, |element| {
+                state.next_with_suumo_element(element.clone());
+                step += 1;
+                let value = json!({
+                    "step": step,
+                    "element": format!("{:?}", element),
+                    "text": element.as_str(),
+                    "state": state.index(),
+                });
+                let separator: &[u8] = if step == 1 { b"\n  " } else { b",\n  " };
+                writer.write_all(separator).map_err(write_error)?;
+                serde_json::to_writer(&mut writer, &value).map_err(|e| e.to_string())
+            })?;
+            writer.write_all(b"\n]\n").map_err(write_error)?;
+        }
+        Format::Binary => {
+            let mut encoder = TranscriptEncoder::new(&mut writer, None).map_err(write_error)?;
+            transcript::read_elements(reader, |element| {
+                encoder.push(&element).map_err(write_error)
+            })?;
+            encoder.finish().map_err(write_error)?;
+        }
+    }
 
-    if let Some(limit) = limit {
-        println!("{}", suumo_state.collect_to_string_nth(limit));
+    writer.flush().map_err(write_error)
+}
+
+// A transcript that stops before the jingle is valid, but exits like a run
+// that hit its limit.
+fn verify(input: Option<PathBuf>, global: &GlobalArgs) -> Result<ExitCode, String> {
+    let verification = transcript::verify(open_input(input)?)?;
+    let mut writer = create_output(global.output.as_deref())?;
+
+    match global.format {
+        Format::Text => writeln!(
+            writer,
+            "ok: {} elements, ended in {:?}{}",
+            verification.elements,
+            verification.state,
+            if verification.is_terminated() {
+                ""
+            } else {
+                " before the jingle"
+            }
+        )
+        .map_err(write_error)?,
+        Format::Json => writeln!(
+            writer,
+            "{}",
+            json!({
+                "elements": verification.elements,
+                "state": verification.state.index(),
+                "terminated": verification.is_terminated(),
+            })
+        )
+        .map_err(write_error)?,
+        Format::Binary => return Err("verify does not support --format binary".to_string()),
+    }
+    writer.flush().map_err(write_error)?;
+
+    Ok(if verification.is_terminated() {
+        ExitCode::SUCCESS
     } else {
-        while let Some((_, element)) = suumo_state.next() {
-            print!("{}", element);
+        ExitCode::from(EXIT_LIMIT_HIT)
+    })
+}
+
+fn expected_stats(global: &GlobalArgs) -> Result<(), String> {
+    let elements = stats::expected_termination_steps();
+    // By Wald's identity the expected output size is the expected number of
+    // elements times the mean size of an element.
+    let total_len: usize = SuumoElement::ALL.iter().map(|e| e.as_str().len()).sum();
+    let bytes = Ratio::new(
+        &elements * BigUint::from(total_len),
+        BigUint::from(SuumoElement::ALL.len()),
+    );
+    let mut writer = create_output(global.output.as_deref())?;
+
+    match global.format {
+        Format::Text => writeln!(
+            writer,
+            "expected elements: {}\nexpected bytes: {}",
+            elements, bytes
+        )
+        .map_err(write_error)?,
+        Format::Json => writeln!(
+            writer,
+            "{}",
+            json!({
+                "expected_elements": elements.to_u64(),
+                "expected_bytes": bytes.to_f64(),
+            })
+        )
+        .map_err(write_error)?,
+        Format::Binary => return Err("stats does not support --format binary".to_string()),
+    }
+
+    writer.flush().map_err(write_error)
+}
+
+fn state_diagram(global: &GlobalArgs) -> Result<(), String> {
+    let mut writer = create_output(global.output.as_deref())?;
+
+    match global.format {
+        Format::Text => write!(writer, "{}", diagram::mermaid()).map_err(write_error)?,
+        Format::Json => writeln!(
+            writer,
+            "{}",
+            json!({
+                "states": SuumoState::ALL.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>(),
+                "elements": SuumoElement::ALL
+                    .iter()
+                    .map(|e| json!({ "name": format!("{:?}", e), "text": e.as_str() }))
+                    .collect::<Vec<_>>(),
+                "transitions": SuumoState::TRANSITIONS,
+            })
+        )
+        .map_err(write_error)?,
+        Format::Binary => return Err("diagram does not support --format binary".to_string()),
+    }
+
+    writer.flush().map_err(write_error)
+}
+
+fn cdf(steps: usize, exact: bool, global: &GlobalArgs) -> Result<(), String> {
+    text_only(global, "cdf")?;
+    let mut writer = create_output(global.output.as_deref())?;
+
+    writeln!(writer, "step,probability").map_err(write_error)?;
+    for (step, p) in stats::TerminationCdf::new().take(steps + 1).enumerate() {
+        if exact {
+            writeln!(writer, "{},{}", step, p).map_err(write_error)?;
+        } else {
+            writeln!(writer, "{},{:e}", step, p.to_f64().unwrap()).map_err(write_error)?;
+        }
+    }
+
+    writer.flush().map_err(write_error)
+}
+
+fn analyze(file: Option<PathBuf>, global: &GlobalArgs) -> Result<(), String> {
+    text_only(global, "analyze")?;
+    let analysis = Analysis::from_reader(open_input(file)?)?;
+    let mut writer = create_output(global.output.as_deref())?;
+
+    write_analysis(&mut writer, &analysis)
+        .and_then(|_| writer.flush())
+        .map_err(write_error)
+}
+
+fn write_analysis(out: &mut impl Write, analysis: &Analysis) -> io::Result<()> {
+    writeln!(out, "elements: {}", analysis.total)?;
+    writeln!(out, "completed runs: {}", analysis.completed)?;
+    writeln!(out)?;
+
+    writeln!(out, "element counts:")?;
+    for (element, count) in SuumoElement::ALL.iter().zip(analysis.counts) {
+        writeln!(
+            out,
+            "  {:<12} {:>12} {:>8.4}%",
+            format!("{:?}", element),
+            count,
+            count as f64 / analysis.total.max(1) as f64 * 100.0
+        )?;
+    }
+    let (statistic, p) = analysis.chi_squared();
+    writeln!(
+        out,
+        "  chi-squared vs uniform: {:.4} (df = 6, p = {:.4})",
+        statistic, p
+    )?;
+    writeln!(out)?;
+
+    writeln!(out, "bigrams (row: previous, column: next):")?;
+    write!(out, "  {:<12}", "")?;
+    for element in SuumoElement::ALL.iter() {
+        write!(out, " {:>12}", format!("{:?}", element))?;
+    }
+    writeln!(out)?;
+    for (element, row) in SuumoElement::ALL.iter().zip(analysis.bigrams) {
+        write!(out, "  {:<12}", format!("{:?}", element))?;
+        for count in row {
+            write!(out, " {:>12}", count)?;
+        }
+        writeln!(out)?;
+    }
+    let (statistic, p) = analysis.bigram_chi_squared();
+    writeln!(
+        out,
+        "  chi-squared vs uniform: {:.4} (df = 48, p = {:.4})",
+        statistic, p
+    )?;
+    writeln!(out)?;
+
+    writeln!(out, "trigrams:")?;
+    for (a, table) in SuumoElement::ALL.iter().zip(analysis.trigrams) {
+        for (b, row) in SuumoElement::ALL.iter().zip(table) {
+            for (c, count) in SuumoElement::ALL.iter().zip(row) {
+                writeln!(
+                    out,
+                    "  {:<38} {:>12}",
+                    format!("{:?} {:?} {:?}", a, b, c),
+                    count
+                )?;
+            }
         }
     }
+    let (statistic, p) = analysis.trigram_chi_squared();
+    writeln!(
+        out,
+        "  chi-squared vs uniform: {:.4} (df = 342, p = {:.4})",
+        statistic, p
+    )?;
+    writeln!(out)?;
+
+    writeln!(
+        out,
+        "longest partial match: {} elements (ending at element {})",
+        analysis.longest_prefix, analysis.longest_prefix_end
+    )?;
+    writeln!(out, "partial matches by length:")?;
+    for (len, count) in analysis.prefix_counts.iter().enumerate().skip(1) {
+        writeln!(out, "  {:>2} {:>12}", len, count)?;
+    }
+
+    Ok(())
+}
+
+fn summarize(file: Option<PathBuf>, depth: usize, global: &GlobalArgs) -> Result<(), String> {
+    text_only(global, "summarize")?;
+    let reader = open_input(file)?;
+    let mut writer = create_output(global.output.as_deref())?;
+
+    let mut summarizer = Summarizer::new(depth);
+    transcript::read_elements(reader, |element| {
+        summarizer.push(&element);
+        for segment in summarizer.drain_segments() {
+            writeln!(writer, "{}", segment).map_err(write_error)?;
+        }
+        Ok(())
+    })?;
+    for segment in summarizer.finish() {
+        writeln!(writer, "{}", segment).map_err(write_error)?;
+    }
+
+    writer.flush().map_err(write_error)
+}
+
+// The direction is detected from the input, so `--format` is not used.
+fn convert(input: Option<PathBuf>, global: &GlobalArgs) -> Result<(), String> {
+    convert_transcript(open_input(input)?, create_output(global.output.as_deref())?)
+}
+
+fn convert_transcript(reader: impl Read, mut writer: impl Write) -> Result<(), String> {
+    let (binary, reader) = transcript::detect(reader)?;
+    let write_error = |e: io::Error| format!("Failed to write transcript: {}", e);
+
+    if binary {
+        for element in TranscriptDecoder::new(reader)? {
+            writer
+                .write_all(element?.as_str().as_bytes())
+                .map_err(write_error)?;
+        }
+        writeln!(writer).map_err(write_error)?;
+        writer.flush().map_err(write_error)
+    } else {
+        let mut encoder = TranscriptEncoder::new(writer, None).map_err(write_error)?;
+        transcript::read_text(reader, |element| {
+            encoder.push(&element).map_err(write_error)
+        })?;
+        encoder.finish().map(|_| ()).map_err(write_error)
+    }
+}
+
+fn completions(shell: Shell, global: &GlobalArgs) -> Result<(), String> {
+    let mut writer = create_output(global.output.as_deref())?;
+    clap_complete::generate(shell, &mut Args::command(), "suumo-gen", &mut writer);
+
+    writer.flush().map_err(write_error)
+}
+
+// Options added by features are only in the pages of a build with them.
+fn man_pages(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir)
+        .and_then(|_| clap_mangen::generate_to(Args::command().name("suumo-gen"), dir))
+        .map_err(|e| format!("Failed to write the man pages to {}: {}", dir.display(), e))
 }
@@ -0,0 +1,386 @@
+use std::{path::PathBuf, time::Duration};
+
+use clap::{
+    error::ErrorKind, parser::ValueSource, ArgMatches, Args as _, CommandFactory, Parser,
+    Subcommand, ValueEnum,
+};
+use clap_complete::Shell;
+use suumo_gen::output::{parse_size, Compression};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "suumo_gen",
+    version,
+    about,
+    author,
+    after_help = "Defaults for --seed, --format, --output, --threads, --rendering and \
+                  --pattern-file can be set in ./suumo-gen.toml, suumo-gen/suumo-gen.toml in \
+                  the config directory or the SUUMO_GEN_SEED, SUUMO_GEN_FORMAT, \
+                  SUUMO_GEN_OUTPUT, SUUMO_GEN_THREADS, SUUMO_GEN_RENDERING and \
+                  SUUMO_GEN_PATTERN_FILE environment variables. Element weights have no setting \
+                  of their own: they are part of the pattern file.\n\n\
+                  Exit status: 0 when the jingle was generated, 1 on errors, 2 when the run \
+                  stopped before the jingle because of --limit or --timeout, and 130 when it \
+                  was interrupted with Ctrl-C."
+)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    // Running without a subcommand is the same as `run`. These are rejected
+    // before other subcommands by `check_run_args`, as
+    // `args_conflicts_with_subcommands` would also reject the global options.
+    #[command(flatten)]
+    pub run: RunArgs,
+
+    #[command(flatten)]
+    pub global: GlobalArgs,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct GlobalArgs {
+    /// Seed the random number generator to make the run reproducible
+    #[arg(long, global = true)]
+    pub seed: Option<u64>,
+
+    /// Output format: text, json or binary (not every command supports all)
+    #[arg(
+        short,
+        long,
+        global = true,
+        value_enum,
+        default_value_t = Format::Text
+    )]
+    pub format: Format,
+
+    /// Write the output to this file instead of stdout
+    #[arg(short, long, global = true)]
+    pub output: Option<PathBuf>,
+}
+impl GlobalArgs {
+    // Drops a format or output that is not from the command line when
+    // `command` can't use it, so that a default from suumo-gen.toml or the
+    // environment that is meant for one command doesn't break the others. Only
+    // `run` uses a default output.
+    pub fn for_command(mut self, command: &Command, matches: &ArgMatches) -> Self {
+        if !is_given(matches, "format") && !command.formats().contains(&self.format) {
+            self.format = Format::Text;
+        }
+        if !is_given(matches, "output") && !matches!(command, Command::Run(_)) {
+            self.output = None;
+        }
+
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Rendering {
+    Elements,
+    Count,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct RunArgs {
+    /// Stop after generating this many elements
+    #[arg(short, long)]
+    pub limit: Option<usize>,
+
+    /// Stop after this long (e.g. 30s, 15m, 1h30m)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Print only the number of generated elements (same as --rendering count)
+    #[arg(short, long)]
+    pub count_only: bool,
+
+    /// What to print: every generated element, or only their count
+    #[arg(long, value_enum, default_value_t = Rendering::Elements)]
+    pub rendering: Rendering,
+
+    /// Generate the jingle described by this TOML pattern file
+    #[arg(short, long)]
+    pub pattern_file: Option<PathBuf>,
+
+    /// Print the last N state transitions to stderr when the run ends
+    #[arg(long, value_name = "N", conflicts_with_all = ["pattern_file", "count_only"])]
+    pub trace: Option<usize>,
+
+    /// Print to stderr whenever the run matches more of the jingle than before
+    #[arg(long, conflicts_with_all = ["pattern_file", "count_only"])]
+    pub progress: bool,
+
+    // Checked against --output when running, as `requires` would not count an
+    // output path from suumo-gen.toml.
+    /// Start a new output file after this many bytes (e.g. 512K, 100M, 1G)
+    #[arg(long, value_parser = parse_size)]
+    pub rotate_size: Option<u64>,
+
+    /// Compress the output files: none, gzip or zstd
+    #[arg(long, default_value = "none")]
+    pub compress: Compression,
+
+    /// Serve Prometheus metrics at http://ADDR/metrics (127.0.0.1:9119 if ADDR
+    /// is omitted) and start a new run whenever one reaches the jingle, until
+    /// stopped
+    #[cfg(feature = "metrics")]
+    #[arg(
+        long,
+        value_name = "ADDR",
+        num_args = 0..=1,
+        default_missing_value = "127.0.0.1:9119",
+        conflicts_with_all = ["pattern_file", "count_only"]
+    )]
+    pub metrics_addr: Option<String>,
+
+    /// Number of generator threads (defaults to the number of CPUs)
+    #[cfg(feature = "multi-thread")]
+    // `--therads` is the original, misspelled name.
+    #[arg(short, long, alias = "therads")]
+    pub threads: Option<usize>,
+}
+impl RunArgs {
+    // Whether the elements are counted instead of printed. The rendering can
+    // come from suumo-gen.toml, so it is checked against the options that
+    // conflict with --count-only when running.
+    pub fn is_count_only(&self) -> bool {
+        self.count_only || self.rendering == Rendering::Count
+    }
+}
+
+// Parses durations like `30`, `500ms`, `90s`, `15m`, `2h` or `1h30m`. A number
+// without a unit is in seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: {}", s);
+    if let Ok(seconds) = s.parse::<u64>() {
+        return Some(Duration::from_secs(seconds))
+            .filter(|duration| !duration.is_zero())
+            .ok_or_else(invalid);
+    }
+
+    let mut millis: u64 = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = rest[digits..]
+            .find(|c: char| c.is_ascii_digit())
+            .map_or(rest.len(), |i| digits + i);
+        let scale = match &rest[digits..unit] {
+            "ms" => 1,
+            "s" => 1000,
+            "m" => 60 * 1000,
+            "h" => 60 * 60 * 1000,
+            "d" => 24 * 60 * 60 * 1000,
+            _ => return Err(invalid()),
+        };
+        millis = rest[..digits]
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(scale))
+            .and_then(|n| n.checked_add(millis))
+            .ok_or_else(invalid)?;
+        rest = &rest[unit..];
+    }
+
+    Some(Duration::from_millis(millis))
+        .filter(|duration| !duration.is_zero())
+        .ok_or_else(invalid)
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Generate elements until the jingle appears (the default command)
+    Run(RunArgs),
+    /// Print every element of a transcript with the state it leads to
+    Parse {
+        /// Transcript to read instead of stdin
+        input: Option<PathBuf>,
+    },
+    /// Check that a transcript is a single run that stops at the jingle
+    Verify {
+        /// Transcript to read instead of stdin
+        input: Option<PathBuf>,
+    },
+    /// Print the expected length of a run
+    Stats,
+    /// Print the state machine as a Mermaid state diagram
+    Diagram,
+    /// Print the probability of reaching the jingle within each step as CSV
+    Cdf {
+        /// Number of steps to print
+        #[arg(short, long)]
+        steps: usize,
+
+        /// Print exact fractions instead of floats
+        #[arg(short, long)]
+        exact: bool,
+    },
+    /// Report element frequencies, n-grams and partial matches of a transcript
+    Analyze {
+        /// Transcript to read instead of stdin
+        file: Option<PathBuf>,
+    },
+    /// Collapse a transcript into noise and the attempts that got far enough
+    Summarize {
+        /// Transcript to read instead of stdin
+        file: Option<PathBuf>,
+
+        /// Only list attempts that matched at least this many elements
+        #[arg(short, long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..=19))]
+        depth: u64,
+    },
+    /// Convert a transcript between text and the compact binary format
+    ///
+    /// The direction is detected from the input: binary transcripts are
+    /// decoded to text and text transcripts are encoded to binary.
+    Convert {
+        /// Transcript to read instead of stdin
+        input: Option<PathBuf>,
+    },
+    /// Print a shell completion script
+    Completions {
+        /// bash, zsh, fish, powershell or elvish
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Write the man pages, suumo-gen.1 and one per subcommand, to a directory
+    Man {
+        /// Directory to write the pages to, created if missing
+        dir: PathBuf,
+    },
+}
+
+impl Command {
+    // The formats the command can write. `convert`, `completions` and `man`
+    // don't use the format.
+    pub fn formats(&self) -> &'static [Format] {
+        const ALL: &[Format] = &[Format::Text, Format::Json, Format::Binary];
+        match self {
+            Self::Run(args) if args.pattern_file.is_some() || args.is_count_only() => {
+                &[Format::Text]
+            }
+            #[cfg(feature = "metrics")]
+            Self::Run(args) if args.metrics_addr.is_some() => &[Format::Text],
+            Self::Run(_) => &[Format::Text, Format::Binary],
+            Self::Parse { .. }
+            | Self::Convert { .. }
+            | Self::Completions { .. }
+            | Self::Man { .. } => ALL,
+            Self::Verify { .. } | Self::Stats | Self::Diagram => &[Format::Text, Format::Json],
+            Self::Cdf { .. } | Self::Analyze { .. } | Self::Summarize { .. } => &[Format::Text],
+        }
+    }
+}
+
+// Whether `id` was given on the command line, before or after the subcommand.
+pub fn is_given(matches: &ArgMatches, id: &str) -> bool {
+    let given = |matches: &ArgMatches| {
+        matches.try_contains_id(id).unwrap_or(false)
+            && matches.value_source(id) == Some(ValueSource::CommandLine)
+    };
+    given(matches)
+        || matches
+            .subcommand()
+            .is_some_and(|(_, matches)| given(matches))
+}
+
+// Fails if any of the options of `run` were given before another subcommand.
+pub fn check_run_args(matches: &ArgMatches) -> Result<(), clap::Error> {
+    if matches!(matches.subcommand_name(), None | Some("run")) {
+        return Ok(());
+    }
+
+    let run = RunArgs::augment_args(clap::Command::new("run"));
+    let given = run
+        .get_arguments()
+        .find(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine));
+    match given.and_then(|arg| arg.get_long()) {
+        Some(long) => Err(Args::command().bin_name("suumo-gen").error(
+            ErrorKind::ArgumentConflict,
+            format!("--{} can only be used with the run subcommand", long),
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::FromArgMatches;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        let matches = Args::command().try_get_matches_from(args)?;
+        check_run_args(&matches)?;
+        Args::from_arg_matches(&matches)
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(24 * 60 * 60)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("0").is_err());
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("1.5s").is_err());
+        assert!(parse_duration("99999999999999999d").is_err());
+    }
+
+    #[test]
+    fn verify_args() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn global_args_and_run_args() {
+        let args = parse(&["suumo-gen", "--limit", "3", "--seed", "1"]).unwrap();
+        assert!(args.command.is_none());
+        assert_eq!((args.run.limit, args.global.seed), (Some(3), Some(1)));
+
+        for argv in [
+            &["suumo-gen", "--seed", "1", "run", "-l", "3"][..],
+            &["suumo-gen", "run", "-l", "3", "--seed", "1"],
+        ] {
+            let args = parse(argv).unwrap();
+            let Some(Command::Run(run)) = args.command else {
+                panic!("expected run: {:?}", argv);
+            };
+            assert_eq!((run.limit, args.global.seed), (Some(3), Some(1)));
+        }
+
+        let args = parse(&["suumo-gen", "-f", "json", "diagram", "-o", "out.json"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Diagram)));
+        assert_eq!(args.global.format, Format::Json);
+        assert_eq!(args.global.output, Some(PathBuf::from("out.json")));
+
+        let error = parse(&["suumo-gen", "--limit", "3", "stats"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+        assert!(parse(&["suumo-gen", "stats", "--limit", "3"]).is_err());
+    }
+
+    #[cfg(feature = "multi-thread")]
+    #[test]
+    fn threads() {
+        for argv in [
+            &["suumo-gen", "--threads", "2"][..],
+            &["suumo-gen", "-t", "2"],
+            &["suumo-gen", "--therads", "2"],
+        ] {
+            assert_eq!(parse(argv).unwrap().run.threads, Some(2), "{:?}", argv);
+        }
+    }
+}
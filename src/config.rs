@@ -0,0 +1,523 @@
+// Defaults from SUUMO_GEN_* environment variables and suumo-gen.toml. They
+// are turned into the default values of the command line arguments, so that,
+// from highest to lowest precedence, arguments, environment variables,
+// ./suumo-gen.toml, the user's suumo-gen.toml and the built-in defaults apply.
+// Both sources go through the same path, so a default never behaves
+// differently depending on where it was set.
+
+use std::{
+    env,
+    ffi::OsString,
+    fmt::Display,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use clap::{error::ErrorKind as ClapErrorKind, ArgMatches, Args as _, CommandFactory, ValueEnum};
+use serde::Deserialize;
+
+use crate::cli::{is_given, Args, Format, Rendering, RunArgs};
+
+pub const FILE_NAME: &str = "suumo-gen.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub seed: Option<u64>,
+    pub format: Option<String>,
+    pub output: Option<PathBuf>,
+    // Rejected by builds without the `multi-thread` feature.
+    pub threads: Option<usize>,
+    pub rendering: Option<String>,
+    // There is no `weights` key: element weights, like the texts and the
+    // jingle itself, come from a pattern file.
+    pub pattern_file: Option<PathBuf>,
+}
+impl Config {
+    pub fn from_toml_str(s: &str) -> Result<Self, String> {
+        let config: Self = toml::from_str(s).map_err(|e| e.to_string())?;
+        // Case sensitive like the arguments.
+        if let Some(format) = &config.format {
+            Format::from_str(format, false).map_err(|_| format!("Invalid format: {}", format))?;
+        }
+        if let Some(rendering) = &config.rendering {
+            Rendering::from_str(rendering, false)
+                .map_err(|_| format!("Invalid rendering: {}", rendering))?;
+        }
+        check_threads(config.threads, "threads")?;
+
+        Ok(config)
+    }
+
+    // Reads the SUUMO_GEN_* variables through `var`. Empty variables count as
+    // unset.
+    pub fn from_env(var: impl Fn(&str) -> Option<OsString>) -> Result<Self, String> {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+        let string = |name: &str| {
+            var(name)
+                .map(|value| {
+                    value
+                        .into_string()
+                        .map_err(|_| format!("Invalid {}: not valid UTF-8", name))
+                })
+                .transpose()
+        };
+        let format = string("SUUMO_GEN_FORMAT")?;
+        if let Some(format) = &format {
+            Format::from_str(format, false)
+                .map_err(|_| format!("Invalid SUUMO_GEN_FORMAT: {}", format))?;
+        }
+        let rendering = string("SUUMO_GEN_RENDERING")?;
+        if let Some(rendering) = &rendering {
+            Rendering::from_str(rendering, false)
+                .map_err(|_| format!("Invalid SUUMO_GEN_RENDERING: {}", rendering))?;
+        }
+        let threads = parse_var(string("SUUMO_GEN_THREADS")?, "SUUMO_GEN_THREADS")?;
+        check_threads(threads, "SUUMO_GEN_THREADS")?;
+
+        Ok(Self {
+            seed: parse_var(string("SUUMO_GEN_SEED")?, "SUUMO_GEN_SEED")?,
+            format,
+            output: var("SUUMO_GEN_OUTPUT").map(PathBuf::from),
+            threads,
+            rendering,
+            pattern_file: var("SUUMO_GEN_PATTERN_FILE").map(PathBuf::from),
+        })
+    }
+
+    // Returns `None` if there is no file at `path`. Relative paths in the file
+    // are relative to the directory that contains it.
+    pub fn from_file(path: &Path) -> Result<Option<Self>, String> {
+        let s = match fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let mut config =
+            Self::from_toml_str(&s).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for path in [&mut config.output, &mut config.pattern_file]
+            .into_iter()
+            .flatten()
+        {
+            *path = dir.join(&*path);
+        }
+
+        Ok(Some(config))
+    }
+
+    // Reads the environment, ./suumo-gen.toml and suumo-gen/suumo-gen.toml in
+    // the user's config directory, in order of preference.
+    pub fn load() -> Result<Self, String> {
+        let mut config = Self::from_env(|name| env::var_os(name))?;
+        if let Some(project) = Self::from_file(Path::new(FILE_NAME))? {
+            config = config.or(project);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(dir) = dirs::config_dir() {
+            if let Some(user) = Self::from_file(&dir.join("suumo-gen").join(FILE_NAME))? {
+                config = config.or(user);
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            seed: self.seed.or(other.seed),
+            format: self.format.or(other.format),
+            output: self.output.or(other.output),
+            threads: self.threads.or(other.threads),
+            rendering: self.rendering.or(other.rendering),
+            pattern_file: self.pattern_file.or(other.pattern_file),
+        }
+    }
+
+    // Sets the values as the defaults of the matching arguments of `command`,
+    // which is `cli::Args::command()`.
+    pub fn apply(&self, mut command: clap::Command) -> clap::Command {
+        let path = |path: &PathBuf| path.to_string_lossy().into_owned();
+        let global = [
+            ("seed", self.seed.map(|seed| seed.to_string())),
+            ("format", self.format.clone()),
+            ("output", self.output.as_ref().map(path)),
+        ];
+        #[cfg(feature = "multi-thread")]
+        let threads = ("threads", self.threads.map(|threads| threads.to_string()));
+        let run = [
+            ("rendering", self.rendering.clone()),
+            ("pattern_file", self.pattern_file.as_ref().map(path)),
+            #[cfg(feature = "multi-thread")]
+            threads,
+        ];
+
+        for (id, value) in global {
+            if let Some(value) = value {
+                command = command.mut_arg(id, |arg| arg.default_value(value));
+            }
+        }
+        // The options of `run` are also accepted without the subcommand.
+        for (id, value) in run {
+            if let Some(value) = value {
+                command = command
+                    .mut_arg(id, |arg| arg.default_value(value.clone()))
+                    .mut_subcommand("run", |run| run.mut_arg(id, |arg| arg.default_value(value)));
+            }
+        }
+
+        command
+    }
+
+    // Rejects arguments that conflict with a default from the config, as clap
+    // only checks conflicts between arguments that were given. `matches` are
+    // those of the command returned by `apply`.
+    pub fn check(&self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        if self.pattern_file.is_none()
+            || is_given(matches, "pattern_file")
+            || !matches!(matches.subcommand_name(), None | Some("run"))
+        {
+            return Ok(());
+        }
+
+        let run = RunArgs::augment_args(clap::Command::new("run"));
+        let given = run.get_arguments().find(|arg| {
+            is_given(matches, arg.get_id().as_str())
+                && run
+                    .get_arg_conflicts_with(arg)
+                    .iter()
+                    .any(|conflict| conflict.get_id() == "pattern_file")
+        });
+        match given.and_then(|arg| arg.get_long()) {
+            Some(long) => Err(Args::command().bin_name("suumo-gen").error(
+                ClapErrorKind::ArgumentConflict,
+                format!(
+                    "--{} cannot be used with the pattern file from suumo-gen.toml or \
+                     SUUMO_GEN_PATTERN_FILE",
+                    long
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+fn check_threads(threads: Option<usize>, name: &str) -> Result<(), String> {
+    if threads.is_some() && !cfg!(feature = "multi-thread") {
+        return Err(format!(
+            "{} is not supported in this build (enable the `multi-thread` feature)",
+            name
+        ));
+    }
+
+    Ok(())
+}
+
+fn parse_var<T: FromStr>(value: Option<String>, name: &str) -> Result<Option<T>, String>
+where
+    T::Err: Display,
+{
+    value
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|e| format!("Invalid {}: {}: {}", name, value, e))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Args, Command, GlobalArgs};
+    use clap::{CommandFactory, FromArgMatches};
+
+    // Parses like `main`, with `args.global` resolved for the command.
+    fn try_parse(config: &Config, args: &[&str]) -> Result<(Command, GlobalArgs), clap::Error> {
+        let matches = config.apply(Args::command()).try_get_matches_from(args)?;
+        config.check(&matches)?;
+        let args = Args::from_arg_matches(&matches)?;
+        let command = args.command.unwrap_or(Command::Run(args.run));
+        let global = args.global.for_command(&command, &matches);
+        Ok((command, global))
+    }
+
+    fn parse(config: &Config, args: &[&str]) -> (Command, GlobalArgs) {
+        try_parse(config, args).unwrap()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Result<Config, String> {
+        Config::from_env(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| OsString::from(value))
+        })
+    }
+
+    #[test]
+    fn config_from_toml_str() {
+        let config = Config::from_toml_str(
+            r#"
+            seed = 19
+            format = "binary"
+            output = "out.bin"
+            rendering = "count"
+            pattern-file = "patterns/suumo.toml"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                seed: Some(19),
+                format: Some("binary".to_string()),
+                output: Some(PathBuf::from("out.bin")),
+                threads: None,
+                rendering: Some("count".to_string()),
+                pattern_file: Some(PathBuf::from("patterns/suumo.toml")),
+            }
+        );
+        assert_eq!(Config::from_toml_str(""), Ok(Config::default()));
+        assert!(Config::from_toml_str("limit = 10").is_err());
+        assert!(Config::from_toml_str("seed = \"19\"").is_err());
+        assert_eq!(
+            Config::from_toml_str("format = \"yaml\""),
+            Err("Invalid format: yaml".to_string())
+        );
+        assert_eq!(
+            Config::from_toml_str("format = \"JSON\""),
+            Err("Invalid format: JSON".to_string())
+        );
+        assert_eq!(
+            Config::from_toml_str("rendering = \"Count\""),
+            Err("Invalid rendering: Count".to_string())
+        );
+
+        let threads = Config::from_toml_str("threads = 4").map(|config| config.threads);
+        if cfg!(feature = "multi-thread") {
+            assert_eq!(threads, Ok(Some(4)));
+        } else {
+            assert_eq!(
+                threads,
+                Err(
+                    "threads is not supported in this build (enable the `multi-thread` feature)"
+                        .to_string()
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn config_from_env() {
+        let config = env(&[
+            ("SUUMO_GEN_SEED", "19"),
+            ("SUUMO_GEN_FORMAT", "binary"),
+            ("SUUMO_GEN_OUTPUT", "out.bin"),
+            ("SUUMO_GEN_RENDERING", "count"),
+            ("SUUMO_GEN_PATTERN_FILE", "patterns/suumo.toml"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config,
+            Config::from_toml_str(
+                r#"
+                seed = 19
+                format = "binary"
+                output = "out.bin"
+                rendering = "count"
+                pattern-file = "patterns/suumo.toml"
+                "#
+            )
+            .unwrap()
+        );
+
+        assert_eq!(env(&[("SUUMO_GEN_SEED", "")]), Ok(Config::default()));
+        assert!(env(&[("SUUMO_GEN_SEED", "-1")]).is_err());
+        assert!(env(&[("SUUMO_GEN_THREADS", "many")]).is_err());
+        assert_eq!(
+            env(&[("SUUMO_GEN_FORMAT", "JSON")]),
+            Err("Invalid SUUMO_GEN_FORMAT: JSON".to_string())
+        );
+        assert_eq!(
+            env(&[("SUUMO_GEN_RENDERING", "text")]),
+            Err("Invalid SUUMO_GEN_RENDERING: text".to_string())
+        );
+
+        let threads = env(&[("SUUMO_GEN_THREADS", "4")]).map(|config| config.threads);
+        if cfg!(feature = "multi-thread") {
+            assert_eq!(threads, Ok(Some(4)));
+        } else {
+            assert!(threads
+                .unwrap_err()
+                .starts_with("SUUMO_GEN_THREADS is not supported"));
+        }
+    }
+
+    #[test]
+    fn config_file_paths() {
+        let dir = std::env::temp_dir().join(format!("suumo-gen-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(FILE_NAME);
+        fs::write(
+            &path,
+            "output = \"out.txt\"\npattern-file = \"/abs.toml\"\n",
+        )
+        .unwrap();
+
+        let config = Config::from_file(&path).unwrap().unwrap();
+        assert_eq!(config.output, Some(dir.join("out.txt")));
+        assert_eq!(config.pattern_file, Some(PathBuf::from("/abs.toml")));
+        assert_eq!(Config::from_file(&dir.join("missing.toml")), Ok(None));
+
+        fs::write(&path, "seed = -1\n").unwrap();
+        assert!(Config::from_file(&path)
+            .unwrap_err()
+            .starts_with(&format!("Invalid {}", path.display())));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn project_config_overrides_user_config() {
+        let project = Config {
+            seed: Some(1),
+            ..Config::default()
+        };
+        let user = Config {
+            seed: Some(2),
+            format: Some("json".to_string()),
+            ..Config::default()
+        };
+
+        let config = project.or(user);
+        assert_eq!(config.seed, Some(1));
+        assert_eq!(config.format.as_deref(), Some("json"));
+    }
+
+    #[test]
+    fn arguments_override_config() {
+        let file = Config {
+            seed: Some(19),
+            format: Some("json".to_string()),
+            output: Some(PathBuf::from("out.txt")),
+            pattern_file: Some(PathBuf::from("pattern.toml")),
+            ..Config::default()
+        };
+        let config = env(&[("SUUMO_GEN_SEED", "7"), ("SUUMO_GEN_FORMAT", "binary")])
+            .unwrap()
+            .or(file.clone());
+
+        let (command, global) = parse(&config, &["suumo-gen", "stats"]);
+        assert!(matches!(command, Command::Stats));
+        assert_eq!(global.seed, Some(7));
+        // The environment wins, but stats can't write binary.
+        assert_eq!(global.format, Format::Text);
+
+        let (command, global) = parse(&file, &["suumo-gen"]);
+        let Command::Run(run) = command else {
+            panic!("expected run");
+        };
+        assert_eq!(global.seed, Some(19));
+        // run can't write JSON.
+        assert_eq!(global.format, Format::Text);
+        assert_eq!(global.output, Some(PathBuf::from("out.txt")));
+        assert_eq!(run.pattern_file, Some(PathBuf::from("pattern.toml")));
+
+        let (command, global) = parse(&config, &["suumo-gen", "run", "--seed", "1", "-f", "text"]);
+        assert_eq!(global.seed, Some(1));
+        assert_eq!(global.format, Format::Text);
+        let Command::Run(run) = command else {
+            panic!("expected run");
+        };
+        assert_eq!(run.pattern_file, Some(PathBuf::from("pattern.toml")));
+
+        let (_, global) = parse(&config, &["suumo-gen", "stats", "-f", "json"]);
+        assert_eq!(global.format, Format::Json);
+        let (_, global) = parse(&config, &["suumo-gen", "stats", "-f", "binary"]);
+        assert_eq!(global.format, Format::Binary);
+
+        let (_, global) = parse(&Config::default(), &["suumo-gen"]);
+        assert_eq!(global.seed, None);
+        assert_eq!(global.format, Format::Text);
+    }
+
+    #[test]
+    fn default_rendering() {
+        let config = Config {
+            rendering: Some("count".to_string()),
+            format: Some("binary".to_string()),
+            ..Config::default()
+        };
+
+        for args in [&["suumo-gen"][..], &["suumo-gen", "run"]] {
+            let (command, global) = parse(&config, args);
+            let Command::Run(run) = command else {
+                panic!("expected run");
+            };
+            assert!(run.is_count_only(), "{:?}", args);
+            // Counting only writes text.
+            assert_eq!(global.format, Format::Text, "{:?}", args);
+        }
+
+        let (command, global) = parse(&config, &["suumo-gen", "--rendering", "elements"]);
+        let Command::Run(run) = command else {
+            panic!("expected run");
+        };
+        assert!(!run.is_count_only());
+        assert_eq!(global.format, Format::Binary);
+
+        let (command, _) = parse(&Config::default(), &["suumo-gen"]);
+        let Command::Run(run) = command else {
+            panic!("expected run");
+        };
+        assert_eq!(run.rendering, Rendering::Elements);
+        assert!(!run.is_count_only());
+    }
+
+    #[test]
+    fn default_output_is_only_for_run() {
+        let config = Config {
+            output: Some(PathBuf::from("out.txt")),
+            ..Config::default()
+        };
+
+        for args in [
+            &["suumo-gen", "diagram"][..],
+            &["suumo-gen", "completions", "bash"],
+            &["suumo-gen", "stats"],
+        ] {
+            assert_eq!(parse(&config, args).1.output, None, "{:?}", args);
+        }
+        let (_, global) = parse(&config, &["suumo-gen", "diagram", "-o", "diagram.mmd"]);
+        assert_eq!(global.output, Some(PathBuf::from("diagram.mmd")));
+        let (_, global) = parse(&config, &["suumo-gen", "-o", "diagram.mmd", "diagram"]);
+        assert_eq!(global.output, Some(PathBuf::from("diagram.mmd")));
+        let (_, global) = parse(&config, &["suumo-gen", "run"]);
+        assert_eq!(global.output, Some(PathBuf::from("out.txt")));
+    }
+
+    #[test]
+    fn default_pattern_file_conflicts() {
+        let file = Config {
+            pattern_file: Some(PathBuf::from("pattern.toml")),
+            ..Config::default()
+        };
+        let env = env(&[("SUUMO_GEN_PATTERN_FILE", "pattern.toml")]).unwrap();
+
+        for config in [&file, &env] {
+            for args in [
+                &["suumo-gen", "--progress"][..],
+                &["suumo-gen", "run", "--trace", "5"],
+                &["suumo-gen", "--trace", "5"],
+            ] {
+                let error = try_parse(config, args).unwrap_err();
+                assert_eq!(error.kind(), ClapErrorKind::ArgumentConflict, "{:?}", args);
+            }
+            assert!(try_parse(config, &["suumo-gen", "--count-only"]).is_ok());
+            assert!(try_parse(config, &["suumo-gen", "stats"]).is_ok());
+        }
+    }
+}
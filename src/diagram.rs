@@ -0,0 +1,62 @@
+use std::fmt::Write;
+
+use crate::{SuumoElement, SuumoState};
+
+// Renders the state machine as a Mermaid state diagram in the same layout as
+// state-diagram.md: the transitions that keep a partial match are listed one
+// by one, and everything that falls back to s0 is collapsed into one edge.
+pub fn mermaid() -> String {
+    let mut diagram = String::from("stateDiagram-v2\n    [*] --> s0\n");
+    for (state, row) in SuumoState::TRANSITIONS
+        .iter()
+        .enumerate()
+        .take(SuumoState::S19.index())
+    {
+        let mut edges: Vec<(u8, &SuumoElement)> = row
+            .iter()
+            .zip(SuumoElement::ALL.iter())
+            .filter(|(&next, _)| next != 0)
+            .map(|(&next, element)| (next, element))
+            .collect();
+        edges.sort_by_key(|&(next, _)| std::cmp::Reverse(next));
+
+        diagram.push('\n');
+        for (next, element) in edges {
+            let _ = writeln!(diagram, "    s{} --> s{}: {}", state, next, element);
+        }
+        if row.contains(&0) {
+            let _ = writeln!(diagram, "    s{} --> s0: 以外全部", state);
+        }
+    }
+    diagram.push_str("\n    s19 --> [*]\n");
+
+    diagram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mermaid_diagram() {
+        let diagram = mermaid();
+        let lines: Vec<&str> = diagram.lines().collect();
+
+        assert_eq!(lines[0], "stateDiagram-v2");
+        assert_eq!(lines[1], "    [*] --> s0");
+        assert_eq!(lines.last(), Some(&"    s19 --> [*]"));
+        assert!(lines.contains(&"    s0 --> s1: あ❗️ スーモ❗️🌚"));
+        assert!(lines.contains(&"    s0 --> s0: 以外全部"));
+        assert!(lines.contains(&"    s2 --> s3: ダン💥"));
+        assert!(lines.contains(&"    s18 --> s19: ス〜〜〜モ⤵🌞"));
+        assert!(!lines.iter().any(|line| line.starts_with("    s19 --> s")));
+
+        // One forward edge per state, and every state can fall back to s1.
+        for state in 0..19 {
+            let forward = format!("    s{} --> s{}: ", state, state + 1);
+            assert_eq!(lines.iter().filter(|l| l.starts_with(&forward)).count(), 1);
+        }
+        let to_s1 = lines.iter().filter(|l| l.contains("--> s1: ")).count();
+        assert_eq!(to_s1, 19);
+    }
+}
@@ -0,0 +1,198 @@
+// C API, linked into `libsuumo_gen.a` and `libsuumo_gen.so` by the
+// suumo-gen-ffi package. The header in `include/suumo_gen.h` is generated by
+// cbindgen when the crate is built with the `ffi` feature, see
+// `header_is_up_to_date` in tests/ffi.rs.
+
+use std::{
+    ffi::{c_char, CStr},
+    ptr, slice,
+};
+
+use crate::{try_string_to_suumo_elements, SuumoElement, SuumoState};
+
+/// Number of elements. Element indices are `0..SUUMO_ELEMENT_COUNT`.
+pub const SUUMO_ELEMENT_COUNT: u32 = 7;
+/// Index of the state reached once the whole jingle has been fed.
+pub const SUUMO_TERMINAL_STATE: u32 = 19;
+
+/// Opaque state machine handle.
+pub struct SuumoGenState(SuumoState);
+
+/// Creates a state machine in the initial state. Free it with
+/// `suumo_state_free`.
+#[no_mangle]
+pub extern "C" fn suumo_state_new() -> *mut SuumoGenState {
+    Box::into_raw(Box::new(SuumoGenState(SuumoState::new())))
+}
+
+/// Frees a state machine. Does nothing for `NULL`.
+///
+/// # Safety
+///
+/// `state` must be `NULL` or a pointer returned by `suumo_state_new` that has
+/// not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn suumo_state_free(state: *mut SuumoGenState) {
+    if !state.is_null() {
+        drop(Box::from_raw(state));
+    }
+}
+
+/// Feeds the element with index `element` and returns the index of the new
+/// state, or -1 if `state` is `NULL` or `element` is out of range. The
+/// terminal state only transitions to itself.
+///
+/// # Safety
+///
+/// `state` must be `NULL` or a valid pointer returned by `suumo_state_new`.
+#[no_mangle]
+pub unsafe extern "C" fn suumo_state_feed(state: *mut SuumoGenState, element: u32) -> i32 {
+    let Some(state) = state.as_mut() else {
+        return -1;
+    };
+    if element >= SUUMO_ELEMENT_COUNT {
+        return -1;
+    }
+
+    let next = SuumoState::TRANSITIONS[state.0.index()][element as usize];
+    state.0 = SuumoState::ALL[next as usize].clone();
+
+    next as i32
+}
+
+/// Returns the index of the current state, or -1 if `state` is `NULL`.
+///
+/// # Safety
+///
+/// `state` must be `NULL` or a valid pointer returned by `suumo_state_new`.
+#[no_mangle]
+pub unsafe extern "C" fn suumo_state_index(state: *const SuumoGenState) -> i32 {
+    state.as_ref().map_or(-1, |state| state.0.index() as i32)
+}
+
+/// Puts the state machine back into the initial state.
+///
+/// # Safety
+///
+/// `state` must be `NULL` or a valid pointer returned by `suumo_state_new`.
+#[no_mangle]
+pub unsafe extern "C" fn suumo_state_reset(state: *mut SuumoGenState) {
+    if let Some(state) = state.as_mut() {
+        state.0 = SuumoState::new();
+    }
+}
+
+/// Writes the UTF-8 text of the element with index `element` and a
+/// terminating NUL into `buf`, truncating it to fit `len` bytes like
+/// `snprintf`. Returns the length of the full text without the NUL, or -1 if
+/// `element` is out of range.
+///
+/// # Safety
+///
+/// `buf` must be `NULL` with `len` 0, or point to at least `len` writable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn suumo_element_render(element: u32, buf: *mut c_char, len: usize) -> isize {
+    let Some(element) = SuumoElement::ALL.get(element as usize) else {
+        return -1;
+    };
+    let text = element.as_str().as_bytes();
+    if !buf.is_null() && len > 0 {
+        let n = text.len().min(len - 1);
+        ptr::copy_nonoverlapping(text.as_ptr(), buf as *mut u8, n);
+        *buf.add(n) = 0;
+    }
+
+    text.len() as isize
+}
+
+/// Parses the NUL-terminated UTF-8 string `s` into element indices, writes
+/// up to `len` of them into `out` and returns how many elements `s` holds, or
+/// -1 if `s` is `NULL` or not a sequence of elements.
+///
+/// # Safety
+///
+/// `s` must be `NULL` or a NUL-terminated string, and `out` must be `NULL`
+/// with `len` 0 or point to at least `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn suumo_parse(s: *const c_char, out: *mut u8, len: usize) -> isize {
+    if s.is_null() {
+        return -1;
+    }
+    let Ok(s) = CStr::from_ptr(s).to_str() else {
+        return -1;
+    };
+    let Ok(elements) = try_string_to_suumo_elements(s.to_string()) else {
+        return -1;
+    };
+
+    if !out.is_null() {
+        let out = slice::from_raw_parts_mut(out, len);
+        for (out, element) in out.iter_mut().zip(elements.iter()) {
+            *out = element.index() as u8;
+        }
+    }
+
+    elements.len() as isize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ffi_constants() {
+        assert_eq!(SUUMO_ELEMENT_COUNT as usize, SuumoElement::ALL.len());
+        assert_eq!(SUUMO_TERMINAL_STATE as usize, SuumoState::S19.index());
+    }
+
+    #[test]
+    fn ffi_state_machine() {
+        unsafe {
+            let state = suumo_state_new();
+            assert_eq!(suumo_state_index(state), 0);
+            for (i, element) in SuumoElement::JINGLE.iter().enumerate() {
+                assert_eq!(
+                    suumo_state_feed(state, element.index() as u32),
+                    i as i32 + 1
+                );
+            }
+            assert_eq!(suumo_state_index(state), SUUMO_TERMINAL_STATE as i32);
+            assert_eq!(suumo_state_feed(state, 0), SUUMO_TERMINAL_STATE as i32);
+            assert_eq!(suumo_state_feed(state, SUUMO_ELEMENT_COUNT), -1);
+
+            suumo_state_reset(state);
+            assert_eq!(suumo_state_index(state), 0);
+            suumo_state_free(state);
+
+            assert_eq!(suumo_state_feed(ptr::null_mut(), 0), -1);
+            assert_eq!(suumo_state_index(ptr::null()), -1);
+            suumo_state_reset(ptr::null_mut());
+            suumo_state_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn ffi_render_and_parse() {
+        unsafe {
+            let mut buf = [0 as c_char; 32];
+            assert_eq!(suumo_element_render(1, buf.as_mut_ptr(), buf.len()), 10);
+            assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str(), Ok("ダン💥"));
+            assert_eq!(suumo_element_render(1, buf.as_mut_ptr(), 4), 10);
+            assert_eq!(
+                CStr::from_ptr(buf.as_ptr()).to_bytes(),
+                &"ダ".as_bytes()[..3]
+            );
+            assert_eq!(suumo_element_render(1, ptr::null_mut(), 0), 10);
+            assert_eq!(suumo_element_render(7, buf.as_mut_ptr(), buf.len()), -1);
+
+            let mut out = [0u8; 2];
+            let s = c"ダン💥シャーン🎶スモ🌝";
+            assert_eq!(suumo_parse(s.as_ptr(), out.as_mut_ptr(), out.len()), 3);
+            assert_eq!(out, [1, 2]);
+            assert_eq!(suumo_parse(s.as_ptr(), ptr::null_mut(), 0), 3);
+            assert_eq!(suumo_parse(c"ダン".as_ptr(), out.as_mut_ptr(), 2), -1);
+            assert_eq!(suumo_parse(ptr::null(), out.as_mut_ptr(), 2), -1);
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+use crate::{history::Transition, observer::TransitionObserver, SuumoElement, SuumoState};
+
+pub use suumo_gen_core::generator::ElementGenerator;
+
+const TERMINAL: u8 = 19;
+const BUFFER_SIZE: usize = 1 << 16;
+const MAX_ELEMENT_LEN: usize = 32;
+const STOP_CHECK_INTERVAL: u64 = 1 << 16;
+
+// How far a run got. `deepest` is the index of the deepest state reached,
+// i.e. the longest partial match of the jingle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunProgress {
+    pub steps: u64,
+    pub deepest: usize,
+}
+
+// Seeds from `seed` when given, so that runs can be reproduced, and from the
+// OS otherwise.
+pub fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+// Generates elements from `state` and writes them to `writer` until the jingle
+// has been written or `limit` elements have been written, and returns the
+// number of elements written. `state` is left at the state after the last
+// written element.
+pub fn write_run<R: RngCore, W: Write>(
+    state: &mut SuumoState,
+    generator: &mut ElementGenerator<R>,
+    writer: &mut W,
+    limit: Option<u64>,
+) -> io::Result<u64> {
+    write_run_until(state, generator, writer, limit, &AtomicBool::new(false))
+        .map(|progress| progress.steps)
+}
+
+// Like `write_run`, but also stops once `stop` is set. `stop` is checked
+// whenever the buffer is written, so only whole elements are ever written.
+pub fn write_run_until<R: RngCore, W: Write>(
+    state: &mut SuumoState,
+    generator: &mut ElementGenerator<R>,
+    writer: &mut W,
+    limit: Option<u64>,
+    stop: &AtomicBool,
+) -> io::Result<RunProgress> {
+    write_run_observed_until(state, generator, writer, limit, &mut (), stop)
+}
+
+// Like `write_run_until`, but also reports every transition to `observer`
+// like `observer::run_observed` does. The elements are written in batches, so
+// the observer sees a transition before its element reaches `writer`. With
+// `()` as the observer this is as fast as `write_run_until`.
+pub fn write_run_observed_until<R: RngCore, W: Write, O: TransitionObserver + ?Sized>(
+    state: &mut SuumoState,
+    generator: &mut ElementGenerator<R>,
+    writer: &mut W,
+    limit: Option<u64>,
+    observer: &mut O,
+    stop: &AtomicBool,
+) -> io::Result<RunProgress> {
+    // Every element is copied as a fixed-size, zero-padded block and the
+    // cursor is advanced by its real length, which avoids a variable-length
+    // copy per element.
+    let mut texts = [[0; MAX_ELEMENT_LEN]; 7];
+    let mut lens = [0; 7];
+    for (i, element) in SuumoElement::ALL.iter().enumerate() {
+        let text = element.as_str().as_bytes();
+        texts[i][..text.len()].copy_from_slice(text);
+        lens[i] = text.len();
+    }
+
+    let transitions = &SuumoState::TRANSITIONS;
+    let limit = limit.unwrap_or(u64::MAX);
+    let mut current = state.index() as u8;
+    let mut deepest = current;
+    let mut count = 0;
+    let mut buffer = vec![0; BUFFER_SIZE];
+    let mut len = 0;
+    while current != TERMINAL && count < limit {
+        let element = generator.next_index();
+        let from = current;
+        current = transitions[current as usize][element];
+        buffer[len..len + MAX_ELEMENT_LEN].copy_from_slice(&texts[element]);
+        len += lens[element];
+        count += 1;
+
+        if let Err(e) = notify(observer, count, from, element, current, &mut deepest) {
+            *state = SuumoState::ALL[current as usize].clone();
+            return Err(e);
+        }
+        if len > BUFFER_SIZE - MAX_ELEMENT_LEN {
+            if let Err(e) = writer.write_all(&buffer[..len]) {
+                *state = SuumoState::ALL[current as usize].clone();
+                return Err(e);
+            }
+            len = 0;
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+        }
+    }
+    *state = SuumoState::ALL[current as usize].clone();
+    writer.write_all(&buffer[..len])?;
+
+    Ok(RunProgress {
+        steps: count,
+        deepest: deepest as usize,
+    })
+}
+
+#[inline(always)]
+fn notify<O: TransitionObserver + ?Sized>(
+    observer: &mut O,
+    step: u64,
+    from: u8,
+    element: usize,
+    to: u8,
+    deepest: &mut u8,
+) -> io::Result<()> {
+    let transition = Transition {
+        from: SuumoState::ALL[from as usize].clone(),
+        element: SuumoElement::ALL[element].clone(),
+        to: SuumoState::ALL[to as usize].clone(),
+    };
+    observer.on_step(step, &transition)?;
+    if transition.is_reset() {
+        observer.on_reset(step, &transition)?;
+    }
+    if to > *deepest {
+        *deepest = to;
+        observer.on_new_max_depth(step, to as usize)?;
+    }
+    if to == TERMINAL {
+        observer.on_terminate(step)?;
+    }
+
+    Ok(())
+}
+
+// Like `SuumoState::run_until_terminal_nth`, but also stops once `stop` is
+// set, which is checked every `STOP_CHECK_INTERVAL` elements.
+pub fn count_run_until<R: RngCore>(
+    state: &mut SuumoState,
+    generator: &mut ElementGenerator<R>,
+    limit: Option<u64>,
+    stop: &AtomicBool,
+) -> RunProgress {
+    let transitions = &SuumoState::TRANSITIONS;
+    let limit = limit.unwrap_or(u64::MAX);
+    let mut current = state.index() as u8;
+    let mut deepest = current;
+    let mut count = 0;
+    while current != TERMINAL && count < limit {
+        current = transitions[current as usize][generator.next_index()];
+        deepest = deepest.max(current);
+        count += 1;
+
+        if count % STOP_CHECK_INTERVAL == 0 && stop.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+    *state = SuumoState::ALL[current as usize].clone();
+
+    RunProgress {
+        steps: count,
+        deepest: deepest as usize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::{chi_squared_p_value, chi_squared_uniform};
+
+    const JINGLE: &str = "あ❗️ スーモ❗️🌚ダン💥ダン💥ダン💥シャーン🎶スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚ス〜〜〜モ⤴🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝ス〜〜〜モ⤵🌞";
+
+    // Replays the given 3-bit values, 21 per word.
+    struct ScriptedRng(Vec<u64>);
+    impl ScriptedRng {
+        fn new(values: &[u64]) -> Self {
+            let words = values
+                .chunks(21)
+                .rev()
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .rev()
+                        .fold(0, |word, &value| (word << 3) | value)
+                })
+                .collect();
+            Self(words)
+        }
+    }
+    impl RngCore for ScriptedRng {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0.pop().unwrap()
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for chunk in dest.chunks_mut(8) {
+                let bytes = self.next_u64().to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    fn jingle_indices() -> Vec<u64> {
+        crate::try_string_to_suumo_elements(JINGLE.to_string())
+            .unwrap()
+            .iter()
+            .map(|e| e.index() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn element_generator_rejects_seven() {
+        let mut generator = ElementGenerator::new(ScriptedRng::new(&[7, 3, 7, 7, 0, 6]));
+
+        assert_eq!(generator.next(), Some(SuumoElement::SumoFullMoon));
+        assert_eq!(generator.next(), Some(SuumoElement::ASuumo));
+        assert_eq!(generator.next(), Some(SuumoElement::SuuuumoDown));
+    }
+
+    #[test]
+    fn element_generator_fairness() {
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        let mut counts = [0; 7];
+        for _ in 0..700_000 {
+            counts[generator.next_index()] += 1;
+        }
+
+        let p = chi_squared_p_value(chi_squared_uniform(&counts), 6);
+        assert!(p > 0.001, "counts = {:?}, p = {}", counts, p);
+    }
+
+    #[test]
+    fn write_run_until_jingle() {
+        let mut values = vec![1, 0, 1, 4];
+        values.extend(jingle_indices());
+        values.extend([0; 30]);
+        let mut generator = ElementGenerator::new(ScriptedRng::new(&values));
+        let mut suumo_state = SuumoState::new();
+        let mut output = Vec::new();
+
+        let count = write_run(&mut suumo_state, &mut generator, &mut output, None).unwrap();
+        assert_eq!(count, 23);
+        assert_eq!(suumo_state, SuumoState::S19);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("ダン💥あ❗️ スーモ❗️🌚ダン💥スモ🌚{}", JINGLE)
+        );
+
+        let mut output = Vec::new();
+        let count = write_run(&mut suumo_state, &mut generator, &mut output, None).unwrap();
+        assert_eq!(count, 0);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn write_run_with_limit() {
+        let mut values = jingle_indices();
+        values.extend([0; 30]);
+        let mut generator = ElementGenerator::new(ScriptedRng::new(&values));
+        let mut suumo_state = SuumoState::new();
+        let mut output = Vec::new();
+
+        let count = write_run(&mut suumo_state, &mut generator, &mut output, Some(4)).unwrap();
+        assert_eq!(count, 4);
+        assert_eq!(suumo_state, SuumoState::S4);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "あ❗️ スーモ❗️🌚ダン💥ダン💥ダン💥"
+        );
+
+        let mut output = Vec::new();
+        let count = write_run(&mut suumo_state, &mut generator, &mut output, Some(100)).unwrap();
+        assert_eq!(count, 15);
+        assert_eq!(suumo_state, SuumoState::S19);
+    }
+
+    #[test]
+    fn write_run_reports_deepest_state() {
+        let mut values = vec![1, 0, 1, 4];
+        values.extend(jingle_indices());
+        let mut generator = ElementGenerator::new(ScriptedRng::new(&values));
+        let mut suumo_state = SuumoState::new();
+        let stop = AtomicBool::new(false);
+
+        let progress = write_run_until(
+            &mut suumo_state,
+            &mut generator,
+            &mut io::sink(),
+            Some(4),
+            &stop,
+        )
+        .unwrap();
+        assert_eq!(
+            progress,
+            RunProgress {
+                steps: 4,
+                deepest: 2
+            }
+        );
+        assert_eq!(suumo_state, SuumoState::S0);
+
+        let progress = write_run_until(
+            &mut suumo_state,
+            &mut generator,
+            &mut io::sink(),
+            None,
+            &stop,
+        )
+        .unwrap();
+        assert_eq!(
+            progress,
+            RunProgress {
+                steps: 19,
+                deepest: 19
+            }
+        );
+    }
+
+    #[test]
+    fn write_run_until_stopped() {
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        let mut suumo_state = SuumoState::new();
+        let mut output = Vec::new();
+        let stop = AtomicBool::new(true);
+
+        // Stops after the first full buffer, between two elements.
+        let progress =
+            write_run_until(&mut suumo_state, &mut generator, &mut output, None, &stop).unwrap();
+        assert!(output.len() > BUFFER_SIZE - MAX_ELEMENT_LEN && output.len() <= BUFFER_SIZE);
+        let elements =
+            crate::try_string_to_suumo_elements(String::from_utf8(output).unwrap()).unwrap();
+        assert_eq!(elements.len() as u64, progress.steps);
+        assert!(progress.deepest < 19);
+    }
+
+    #[test]
+    fn write_run_observed_matches_run_observed() {
+        use crate::{
+            history::SuumoHistory,
+            observer::{run_observed, WriteObserver},
+        };
+
+        let mut values = vec![1, 0, 1, 4];
+        values.extend(jingle_indices());
+        values.extend([0; 30]);
+        let scripted = || ElementGenerator::new(ScriptedRng::new(&values));
+        let seeded = || ElementGenerator::new(StdRng::seed_from_u64(19));
+        let stop = AtomicBool::new(false);
+
+        let mut expected = (SuumoState::new(), SuumoHistory::new(64), Vec::new());
+        let mut observers: Vec<&mut dyn TransitionObserver> = vec![&mut expected.1];
+        let mut writer = WriteObserver::new(&mut expected.2);
+        observers.push(&mut writer);
+        run_observed(&mut expected.0, &mut scripted(), None, &mut observers).unwrap();
+
+        let mut history = SuumoHistory::new(64);
+        let mut output = Vec::new();
+        let mut state = SuumoState::new();
+        let progress = write_run_observed_until(
+            &mut state,
+            &mut scripted(),
+            &mut output,
+            None,
+            &mut history,
+            &stop,
+        )
+        .unwrap();
+        assert_eq!(progress.steps, 23);
+        assert_eq!(state, expected.0);
+        assert_eq!(output, expected.2);
+        assert_eq!(history.steps(), expected.1.steps());
+        assert!(history.transitions().eq(expected.1.transitions()));
+
+        let mut expected = SuumoHistory::new(16);
+        run_observed(
+            &mut SuumoState::new(),
+            &mut seeded(),
+            Some(100_000),
+            &mut expected,
+        )
+        .unwrap();
+        let mut history = SuumoHistory::new(16);
+        let progress = write_run_observed_until(
+            &mut SuumoState::new(),
+            &mut seeded(),
+            &mut io::sink(),
+            Some(100_000),
+            &mut history,
+            &stop,
+        )
+        .unwrap();
+        assert_eq!(progress.steps, 100_000);
+        assert_eq!(history.state(), expected.state());
+        assert_eq!(history.last_reset(), expected.last_reset());
+        assert!(history.transitions().eq(expected.transitions()));
+    }
+
+    #[test]
+    fn count_run_until_matches_run_until_terminal() {
+        let stop = AtomicBool::new(false);
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        let mut suumo_state = SuumoState::new();
+        let progress = count_run_until(&mut suumo_state, &mut generator, Some(100_000), &stop);
+
+        let mut expected_state = SuumoState::new();
+        let steps = expected_state.run_until_terminal_nth(&mut StdRng::seed_from_u64(19), 100_000);
+        assert_eq!(progress.steps, steps);
+        assert_eq!(suumo_state, expected_state);
+        assert!(progress.deepest >= suumo_state.index());
+
+        let stop = AtomicBool::new(true);
+        let progress = count_run_until(&mut SuumoState::new(), &mut generator, None, &stop);
+        assert_eq!(progress.steps, STOP_CHECK_INTERVAL);
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Display},
+    io,
+};
+
+use crate::{observer::TransitionObserver, SuumoElement, SuumoState};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub from: SuumoState,
+    pub element: SuumoElement,
+    pub to: SuumoState,
+}
+impl Transition {
+    // Whether this transition broke a partial match instead of extending it.
+    pub fn is_reset(&self) -> bool {
+        self.from != SuumoState::S0 && self.to.index() != self.from.index() + 1
+    }
+}
+impl Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} --{}--> {:?}", self.from, self.element, self.to)
+    }
+}
+
+// `SuumoState` that remembers its last `capacity` transitions and the last
+// transition that broke a partial match.
+#[derive(Debug, Clone)]
+pub struct SuumoHistory {
+    state: SuumoState,
+    capacity: usize,
+    transitions: VecDeque<Transition>,
+    last_reset: Option<Transition>,
+    steps: u64,
+}
+impl SuumoHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_state(SuumoState::new(), capacity)
+    }
+
+    pub fn with_state(state: SuumoState, capacity: usize) -> Self {
+        Self {
+            state,
+            capacity,
+            transitions: VecDeque::with_capacity(capacity),
+            last_reset: None,
+            steps: 0,
+        }
+    }
+
+    pub fn state(&self) -> &SuumoState {
+        &self.state
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // Oldest first.
+    pub fn transitions(&self) -> impl DoubleEndedIterator<Item = &Transition> + '_ {
+        self.transitions.iter()
+    }
+
+    pub fn last_reset(&self) -> Option<&Transition> {
+        self.last_reset.as_ref()
+    }
+
+    pub fn next_with_suumo_element(&mut self, next_element: SuumoElement) -> Option<SuumoState> {
+        let from = self.state.clone();
+        let to = self.state.next_with_suumo_element(next_element.clone())?;
+        self.record(Transition {
+            from,
+            element: next_element,
+            to: to.clone(),
+        });
+
+        Some(to)
+    }
+
+    fn record(&mut self, transition: Transition) {
+        self.steps += 1;
+        if transition.is_reset() {
+            self.last_reset = Some(transition.clone());
+        }
+        if self.capacity > 0 {
+            if self.transitions.len() == self.capacity {
+                self.transitions.pop_front();
+            }
+            self.transitions.push_back(transition);
+        }
+    }
+}
+// Follows a run driven by `observer::run_observed`, which has to start from
+// the same state as this history.
+impl TransitionObserver for SuumoHistory {
+    fn on_step(&mut self, _step: u64, transition: &Transition) -> io::Result<()> {
+        self.state = transition.to.clone();
+        self.record(transition.clone());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_ring_buffer() {
+        let mut history = SuumoHistory::new(3);
+        for element in SuumoElement::JINGLE.iter().take(4) {
+            history.next_with_suumo_element(element.clone());
+        }
+
+        assert_eq!(history.state(), &SuumoState::S4);
+        assert_eq!(history.steps(), 4);
+        assert_eq!(
+            history
+                .transitions()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "S1 --ダン💥--> S2",
+                "S2 --ダン💥--> S3",
+                "S3 --ダン💥--> S4"
+            ]
+        );
+        assert_eq!(history.last_reset(), None);
+
+        let mut empty = SuumoHistory::new(0);
+        empty.next_with_suumo_element(SuumoElement::Dan);
+        assert_eq!(empty.transitions().count(), 0);
+        assert_eq!(empty.steps(), 1);
+    }
+
+    #[test]
+    fn history_last_reset() {
+        let mut history = SuumoHistory::new(10);
+        for element in [
+            SuumoElement::Dan,
+            SuumoElement::ASuumo,
+            SuumoElement::Dan,
+            SuumoElement::ASuumo,
+            SuumoElement::Dan,
+            SuumoElement::Shaan,
+            SuumoElement::Dan,
+        ] {
+            history.next_with_suumo_element(element);
+        }
+
+        // "あ❗️ スーモ❗️🌚" from S2 restarts at S1, then "シャーン🎶" from S2 breaks it.
+        assert_eq!(
+            history.last_reset(),
+            Some(&Transition {
+                from: SuumoState::S2,
+                element: SuumoElement::Shaan,
+                to: SuumoState::S0,
+            })
+        );
+        assert!(!history.transitions().next().unwrap().is_reset());
+        assert_eq!(history.transitions().filter(|t| t.is_reset()).count(), 2);
+    }
+
+    #[test]
+    fn history_stops_at_s19() {
+        let mut history = SuumoHistory::new(100);
+        for element in SuumoElement::JINGLE.iter() {
+            assert!(history.next_with_suumo_element(element.clone()).is_some());
+        }
+
+        assert_eq!(history.state(), &SuumoState::S19);
+        assert_eq!(history.next_with_suumo_element(SuumoElement::Dan), None);
+        assert_eq!(history.steps(), 19);
+        assert_eq!(
+            history.transitions().next_back().unwrap().to_string(),
+            "S18 --ス〜〜〜モ⤵🌞--> S19"
+        );
+    }
+}
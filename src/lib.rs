@@ -1,322 +1,37 @@
-use std::fmt::Display;
-
-use rand::Rng;
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum SuumoElement {
-    ASuumo,
-    Dan,
-    Shaan,
-    SumoFullMoon,
-    SumoNewMoon,
-    SuuuumoUp,
-    SuuuumoDown,
-}
-impl SuumoElement {
-    pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
-        match rng.gen_range(0..7) {
-            0 => Self::ASuumo,
-            1 => Self::Dan,
-            2 => Self::Shaan,
-            3 => Self::SumoFullMoon,
-            4 => Self::SumoNewMoon,
-            5 => Self::SuuuumoUp,
-            6 => Self::SuuuumoDown,
-            _ => unreachable!(),
-        }
-    }
-}
-impl Display for SuumoElement {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::ASuumo => write!(f, "あ❗️ スーモ❗️🌚"),
-            Self::Dan => write!(f, "ダン💥"),
-            Self::Shaan => write!(f, "シャーン🎶"),
-            Self::SumoFullMoon => write!(f, "スモ🌝"),
-            Self::SumoNewMoon => write!(f, "スモ🌚"),
-            Self::SuuuumoUp => write!(f, "ス〜〜〜モ⤴🌝"),
-            Self::SuuuumoDown => write!(f, "ス〜〜〜モ⤵🌞"),
-        }
-    }
-}
-impl From<SuumoElement> for String {
-    fn from(s: SuumoElement) -> String {
-        s.to_string()
-    }
-}
-impl TryFrom<String> for SuumoElement {
-    type Error = String;
-
-    fn try_from(s: String) -> Result<SuumoElement, String> {
-        match s.as_str() {
-            "あ❗️ スーモ❗️🌚" => Ok(Self::ASuumo),
-            "ダン💥" => Ok(Self::Dan),
-            "シャーン🎶" => Ok(Self::Shaan),
-            "スモ🌝" => Ok(Self::SumoFullMoon),
-            "スモ🌚" => Ok(Self::SumoNewMoon),
-            "ス〜〜〜モ⤴🌝" => Ok(Self::SuuuumoUp),
-            "ス〜〜〜モ⤵🌞" => Ok(Self::SuuuumoDown),
-            _ => Err(format!("Invalid string: {}", s)),
-        }
-    }
-}
-pub fn try_string_to_suumo_elements(value: String) -> Result<Vec<SuumoElement>, String> {
-    let mut result = Vec::new();
-    let mut chars = value.chars().peekable();
-    while let Some(&c) = chars.peek() {
-        match c {
-            'あ' => {
-                let string: String = chars.by_ref().take(10).collect();
-                let element = SuumoElement::try_from(string)
-                    .map_err(|_| format!("Invalid string: {}", value))?;
-                result.push(element);
-            }
-            'ダ' => {
-                let string: String = chars.by_ref().take(3).collect();
-                let element = SuumoElement::try_from(string)
-                    .map_err(|_| format!("Invalid string: {}", value))?;
-                result.push(element);
-            }
-            'シ' => {
-                let string: String = chars.by_ref().take(5).collect();
-                let element = SuumoElement::try_from(string)
-                    .map_err(|_| format!("Invalid string: {}", value))?;
-                result.push(element);
-            }
-            'ス' => {
-                let first = chars.next().unwrap();
-                let peek = chars.peek().ok_or(format!("Invalid string: {}", value))?;
-                let count = if peek == &'〜' {
-                    6
-                } else if peek == &'モ' {
-                    2
-                } else {
-                    return Err(format!("Invalid string: {}", value));
-                };
-                let string = format!(
-                    "{}{}",
-                    first,
-                    chars.by_ref().take(count).collect::<String>()
-                );
-                let element = SuumoElement::try_from(string)
-                    .map_err(|_| format!("Invalid string: {}", value))?;
-                result.push(element);
-            }
-            _ => return Err(format!("Invalid string: {}", value)),
-        }
-    }
-
-    Ok(result)
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum SuumoState {
-    S0,
-    S1,
-    S2,
-    S3,
-    S4,
-    S5,
-    S6,
-    S7,
-    S8,
-    S9,
-    S10,
-    S11,
-    S12,
-    S13,
-    S14,
-    S15,
-    S16,
-    S17,
-    S18,
-    S19,
-}
-impl SuumoState {
-    pub fn new() -> Self {
-        Self::S0
-    }
-
-    pub fn next_with_suumo_element(&mut self, next_element: SuumoElement) -> Option<Self> {
-        match self {
-            Self::S1 if next_element == SuumoElement::Dan => {
-                *self = Self::S2;
-
-                Some(Self::S2)
-            }
-            Self::S2 if next_element == SuumoElement::Dan => {
-                *self = Self::S3;
-
-                Some(Self::S3)
-            }
-            Self::S3 if next_element == SuumoElement::Dan => {
-                *self = Self::S4;
-
-                Some(Self::S4)
-            }
-            Self::S4 if next_element == SuumoElement::Shaan => {
-                *self = Self::S5;
-
-                Some(Self::S5)
-            }
-            Self::S5 if next_element == SuumoElement::SumoFullMoon => {
-                *self = Self::S6;
-
-                Some(Self::S6)
-            }
-            Self::S6 if next_element == SuumoElement::SumoNewMoon => {
-                *self = Self::S7;
-
-                Some(Self::S7)
-            }
-            Self::S7 if next_element == SuumoElement::SumoFullMoon => {
-                *self = Self::S8;
-
-                Some(Self::S8)
-            }
-            Self::S8 if next_element == SuumoElement::SumoNewMoon => {
-                *self = Self::S9;
-
-                Some(Self::S9)
-            }
-            Self::S9 if next_element == SuumoElement::SumoFullMoon => {
-                *self = Self::S10;
-
-                Some(Self::S10)
-            }
-            Self::S10 if next_element == SuumoElement::SumoNewMoon => {
-                *self = Self::S11;
-
-                Some(Self::S11)
-            }
-            Self::S11 if next_element == SuumoElement::SuuuumoUp => {
-                *self = Self::S12;
-
-                Some(Self::S12)
-            }
-            Self::S12 if next_element == SuumoElement::SumoNewMoon => {
-                *self = Self::S13;
-
-                Some(Self::S13)
-            }
-            Self::S13 if next_element == SuumoElement::SumoFullMoon => {
-                *self = Self::S14;
-
-                Some(Self::S14)
-            }
-            Self::S14 if next_element == SuumoElement::SumoNewMoon => {
-                *self = Self::S15;
-
-                Some(Self::S15)
-            }
-            Self::S15 if next_element == SuumoElement::SumoFullMoon => {
-                *self = Self::S16;
-
-                Some(Self::S16)
-            }
-            Self::S16 if next_element == SuumoElement::SumoNewMoon => {
-                *self = Self::S17;
-
-                Some(Self::S17)
-            }
-            Self::S17 if next_element == SuumoElement::SumoFullMoon => {
-                *self = Self::S18;
-
-                Some(Self::S18)
-            }
-            Self::S18 if next_element == SuumoElement::SuuuumoDown => {
-                *self = Self::S19;
-
-                Some(Self::S19)
-            }
-            Self::S19 => None,
-            _ if next_element == SuumoElement::ASuumo => {
-                *self = Self::S1;
-
-                Some(Self::S1)
-            }
-            _ => {
-                *self = Self::S0;
-
-                Some(Self::S0)
-            }
-        }
-    }
-
-    pub fn collect_to_string(&mut self) -> String {
-        let mut result = String::new();
-        for (_, element) in self {
-            result.push_str(&element.to_string());
-        }
-
-        result
-    }
-
-    pub fn collect_to_string_nth(&mut self, n: usize) -> String {
-        let mut result = String::new();
-        for _ in 0..n {
-            if let Some((_, element)) = self.next() {
-                result.push_str(&element.to_string());
-            } else {
-                break;
-            }
-        }
-
-        result
-    }
-
-    pub fn collect_to_string_with_suumo_elements(
-        &mut self,
-        elements: impl IntoIterator<Item = SuumoElement>,
-    ) -> String {
-        let mut result = String::new();
-        for element in elements {
-            if let Some(_) = self.next_with_suumo_element(element.clone()) {
-                result.push_str(&element.to_string());
-            } else {
-                break;
-            }
-        }
-
-        result
-    }
-
-    pub fn collect_to_string_with_suumo_elements_nth(
-        &mut self,
-        n: usize,
-        elements: impl IntoIterator<Item = SuumoElement>,
-    ) -> String {
-        let mut result = String::new();
-        for (i, element) in elements.into_iter().enumerate() {
-            if i < n {
-                if let Some(_) = self.next_with_suumo_element(element.clone()) {
-                    result.push_str(&element.to_string());
-                } else {
-                    break;
-                }
-            } else {
-                break;
-            }
-        }
-
-        result
-    }
-}
-impl Iterator for SuumoState {
-    type Item = (SuumoState, SuumoElement);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let next_element = SuumoElement::new();
-        self.next_with_suumo_element(next_element.clone())
-            .map(|state| (state, next_element))
-    }
-}
+pub mod analyze;
+pub mod diagram;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod generator;
+pub mod history;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod observer;
+pub mod output;
+pub mod pattern;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod stats;
+pub mod summary;
+pub mod transcript;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use suumo_gen_core::{
+    try_string_to_suumo_elements, SuumoElement, SuumoElementParser, SuumoState,
+};
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::stats::{chi_squared_p_value, chi_squared_uniform, runs_test};
     use once_cell::sync::Lazy;
+    use proptest::{collection::vec, prelude::*, sample::select};
+    use rand::{
+        distributions::{Distribution, WeightedIndex},
+        rngs::{mock::StepRng, StdRng},
+        SeedableRng,
+    };
 
     static ELEMENTS: Lazy<Vec<SuumoElement>> = Lazy::new(|| {
         vec![
@@ -341,6 +56,57 @@ mod tests {
         assert_eq!(SuumoElement::SuuuumoDown.to_string(), "ス〜〜〜モ⤵🌞");
     }
 
+    fn assert_uniform(elements: &[SuumoElement], alpha: f64) {
+        let mut counts = [0; 7];
+        for element in elements {
+            counts[element.index()] += 1;
+        }
+        let p = chi_squared_p_value(chi_squared_uniform(&counts), 6);
+        assert!(p > alpha, "counts = {:?}, p = {}", counts, p);
+
+        for target in ELEMENTS.iter() {
+            let (z, p) = runs_test(elements.iter().map(|e| e == target));
+            assert!(p > alpha, "{:?}: z = {}, p = {}", target, z, p);
+        }
+    }
+
+    #[test]
+    fn suumo_element_fairness() {
+        let mut rng = StdRng::seed_from_u64(19);
+        let elements: Vec<_> = (0..700_000)
+            .map(|_| SuumoElement::new_with_rng(&mut rng))
+            .collect();
+
+        assert_uniform(&elements, 0.001);
+    }
+
+    #[test]
+    fn suumo_element_fairness_detects_bias() {
+        let mut rng = StdRng::seed_from_u64(19);
+
+        // SuuuumoDown a little more often than the others.
+        let skewed = WeightedIndex::new([10, 10, 10, 10, 10, 10, 11]).unwrap();
+        let mut counts = [0; 7];
+        for _ in 0..70_000 {
+            counts[skewed.sample(&mut rng)] += 1;
+        }
+        assert!(chi_squared_p_value(chi_squared_uniform(&counts), 6) < 0.001);
+
+        // The previous element again a little more often than the others.
+        let sticky: Vec<_> = (0..7)
+            .map(|i| WeightedIndex::new((0..7).map(|j| if i == j { 12 } else { 10 })).unwrap())
+            .collect();
+        let mut previous = 0;
+        let elements: Vec<_> = (0..70_000)
+            .map(|_| {
+                previous = sticky[previous].sample(&mut rng);
+                ELEMENTS[previous].clone()
+            })
+            .collect();
+        let (_, p) = runs_test(elements.iter().map(|e| e == &SuumoElement::Dan));
+        assert!(p < 0.001, "p = {}", p);
+    }
+
     #[test]
     fn suumo_element_from_string() {
         assert_eq!(
@@ -409,6 +175,88 @@ mod tests {
         );
     }
 
+    #[test]
+    fn invalid_string_to_suumo_elements() {
+        assert_eq!(
+            try_string_to_suumo_elements("ダン💥ダン".to_string()),
+            Err("Invalid string: ダン💥ダン".to_string())
+        );
+        assert_eq!(
+            try_string_to_suumo_elements("スX".to_string()),
+            Err("Invalid string: スX".to_string())
+        );
+        assert_eq!(
+            try_string_to_suumo_elements("ダン💥\n".to_string()),
+            Err("Invalid string: ダン💥\n".to_string())
+        );
+    }
+
+    // Inputs that hit the `chars.next().unwrap()` after 'ス' and the fixed
+    // length slices of the original parser: a lone or trailing 'ス', elements
+    // cut short at the end of the input, and slices running into the next
+    // element.
+    #[test]
+    fn parser_regressions() {
+        for s in [
+            "ス",
+            "スモ🌝ス",
+            "ス〜",
+            "スモ",
+            "あ❗️ スーモ❗️",
+            "ス〜〜〜モ⤴",
+            "シャーン",
+            "ダン",
+            "スモダン💥",
+            "ス〜〜〜モ⤴ダン💥",
+            "ダンダン💥",
+            "あダン💥",
+            "ス\u{FE0F}",
+            "モ",
+        ] {
+            let error = format!("Invalid string: {}", s);
+            assert_eq!(
+                try_string_to_suumo_elements(s.to_string()),
+                Err(error.clone())
+            );
+            assert_eq!(SuumoElement::try_from(s.to_string()), Err(error));
+        }
+
+        for element in SuumoElement::ALL.iter() {
+            let s = element.to_string();
+            for (end, _) in s.char_indices().skip(1) {
+                assert!(try_string_to_suumo_elements(s[..end].to_string()).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn suumo_element_parser() {
+        let mut parser = SuumoElementParser::new();
+        let mut elements = Vec::new();
+        for c in "スモ🌚ス〜〜〜モ⤴🌝ダン💥".chars() {
+            if let Some(element) = parser.push(c).unwrap() {
+                elements.push(element);
+            }
+        }
+        assert_eq!(
+            elements,
+            vec![
+                SuumoElement::SumoNewMoon,
+                SuumoElement::SuuuumoUp,
+                SuumoElement::Dan
+            ]
+        );
+        assert_eq!(parser.finish(), Ok(()));
+
+        assert_eq!(parser.push('シ'), Ok(None));
+        assert!(!parser.is_empty());
+        assert_eq!(parser.finish(), Err("Invalid string: シ".to_string()));
+        assert!(parser.is_empty());
+
+        assert_eq!(parser.push('x'), Err("Invalid string: x".to_string()));
+        assert_eq!(parser.push('ダ'), Ok(None));
+    }
+
     #[test]
     fn new_suumo_state() {
         let suumo_state = SuumoState::new();
@@ -931,6 +779,47 @@ mod tests {
         }
     }
 
+    #[test]
+    fn suumo_state_transitions_table() {
+        for (state, element) in SuumoElement::JINGLE.iter().enumerate() {
+            assert_eq!(
+                SuumoState::TRANSITIONS[state][element.index()] as usize,
+                state + 1
+            );
+            assert_eq!(
+                SuumoState::TRANSITIONS[state][SuumoElement::ASuumo.index()],
+                1
+            );
+        }
+        assert_eq!(SuumoState::TRANSITIONS[19], [19; 7]);
+    }
+
+    #[test]
+    fn suumo_state_run_until_terminal() {
+        let mut rng = StdRng::seed_from_u64(19);
+        let mut suumo_state = SuumoState::new();
+        assert_eq!(suumo_state.run_until_terminal_nth(&mut rng, 1000), 1000);
+        assert_ne!(suumo_state, SuumoState::S19);
+
+        let mut suumo_state = SuumoState::S19;
+        assert_eq!(suumo_state.run_until_terminal(&mut rng), 0);
+        assert_eq!(suumo_state.run_until_terminal_nth(&mut rng, 10), 0);
+
+        // The whole jingle fits into the 3-bit chunks of a single word.
+        let word = SuumoElement::JINGLE
+            .iter()
+            .enumerate()
+            .fold(0, |word, (i, element)| {
+                word | (element.index() as u64) << (3 * i)
+            });
+        for state in SuumoState::ALL[..19].iter() {
+            let mut suumo_state = state.clone();
+            let mut rng = StepRng::new(word, 0);
+            assert_eq!(suumo_state.run_until_terminal(&mut rng), 19);
+            assert_eq!(suumo_state, SuumoState::S19);
+        }
+    }
+
     #[test]
     fn suumo_state_collect() {
         let mut suumo_state = SuumoState::new();
@@ -988,4 +877,124 @@ mod tests {
         let collected = suumo_state.collect_to_string_nth(10);
         assert_eq!(try_string_to_suumo_elements(collected).unwrap().len(), 10);
     }
+
+    static JINGLE: Lazy<Vec<SuumoElement>> = Lazy::new(|| {
+        try_string_to_suumo_elements(
+            "あ❗️ スーモ❗️🌚ダン💥ダン💥ダン💥シャーン🎶スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚ス〜〜〜モ⤴🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝ス〜〜〜モ⤵🌞"
+                .to_string(),
+        )
+        .unwrap()
+    });
+
+    fn suumo_element() -> impl Strategy<Value = SuumoElement> {
+        select(SuumoElement::ALL.to_vec())
+    }
+
+    fn non_terminal_suumo_state() -> impl Strategy<Value = SuumoState> {
+        select(SuumoState::ALL[..19].to_vec())
+    }
+
+    fn first_jingle_end(elements: &[SuumoElement]) -> Option<usize> {
+        elements
+            .windows(JINGLE.len())
+            .position(|window| window == JINGLE.as_slice())
+            .map(|i| i + JINGLE.len())
+    }
+
+    proptest! {
+        #[test]
+        fn terminates_at_first_jingle(
+            prefix in vec(suumo_element(), 0..200),
+            suffix in vec(suumo_element(), 0..50),
+        ) {
+            let elements: Vec<_> = prefix
+                .into_iter()
+                .chain(JINGLE.iter().cloned())
+                .chain(suffix)
+                .collect();
+            let expected = first_jingle_end(&elements).unwrap();
+
+            let mut suumo_state = SuumoState::new();
+            let mut steps = 0;
+            for element in elements.iter() {
+                if suumo_state.next_with_suumo_element(element.clone()).is_none() {
+                    break;
+                }
+                steps += 1;
+            }
+            prop_assert_eq!(steps, expected);
+            prop_assert_eq!(suumo_state, SuumoState::S19);
+        }
+
+        #[test]
+        fn never_terminates_without_jingle(elements in vec(suumo_element(), 0..500)) {
+            prop_assume!(first_jingle_end(&elements).is_none());
+
+            let mut suumo_state = SuumoState::new();
+            for element in elements {
+                let next = suumo_state.next_with_suumo_element(element);
+                prop_assert!(next.is_some());
+                prop_assert_ne!(next, Some(SuumoState::S19));
+            }
+        }
+
+        #[test]
+        fn a_suumo_leads_to_s1(state in non_terminal_suumo_state()) {
+            let mut suumo_state = state;
+            prop_assert_eq!(
+                suumo_state.next_with_suumo_element(SuumoElement::ASuumo),
+                Some(SuumoState::S1)
+            );
+            prop_assert_eq!(suumo_state, SuumoState::S1);
+        }
+
+        #[test]
+        fn s19_returns_none(elements in vec(suumo_element(), 1..50)) {
+            let mut suumo_state = SuumoState::S19;
+            for element in elements {
+                prop_assert_eq!(suumo_state.next_with_suumo_element(element), None);
+                prop_assert_eq!(&suumo_state, &SuumoState::S19);
+            }
+        }
+
+        #[test]
+        fn collected_string_round_trips(
+            elements in vec(suumo_element(), 0..300),
+            with_jingle in any::<bool>(),
+        ) {
+            let elements: Vec<_> = if with_jingle {
+                elements.into_iter().chain(JINGLE.iter().cloned()).collect()
+            } else {
+                elements
+            };
+            let consumed = first_jingle_end(&elements).unwrap_or(elements.len());
+
+            let mut suumo_state = SuumoState::new();
+            let collected = suumo_state.collect_to_string_with_suumo_elements(elements.clone());
+            prop_assert_eq!(
+                try_string_to_suumo_elements(collected),
+                Ok(elements[..consumed].to_vec())
+            );
+        }
+
+        #[test]
+        fn parse_arbitrary_string(s in ".*") {
+            if let Ok(elements) = try_string_to_suumo_elements(s.clone()) {
+                let rendered: String = elements.iter().map(SuumoElement::to_string).collect();
+                prop_assert_eq!(&rendered, &s);
+            }
+        }
+
+        #[test]
+        fn parse_jingle_alphabet(chars in vec(select("あ❗️ スーモ🌚ダン💥シャ🎶🌝〜⤴⤵🌞x".chars().collect::<Vec<_>>()), 0..64)) {
+            let s: String = chars.into_iter().collect();
+            if let Ok(elements) = try_string_to_suumo_elements(s.clone()) {
+                let rendered: String = elements.iter().map(SuumoElement::to_string).collect();
+                prop_assert_eq!(&rendered, &s);
+            }
+            if let Ok(element) = SuumoElement::try_from(s.clone()) {
+                prop_assert_eq!(element.to_string(), s);
+            }
+        }
+    }
 }
@@ -0,0 +1,361 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{history::Transition, observer::TransitionObserver, SuumoElement, SuumoState};
+
+// Counters shared by every generator thread. All of them only ever grow, so
+// relaxed atomics are enough.
+#[derive(Debug)]
+pub struct Metrics {
+    elements: [AtomicU64; SuumoElement::ALL.len()],
+    state_entries: [AtomicU64; SuumoState::ALL.len()],
+    resets: AtomicU64,
+    max_depth: AtomicU64,
+    completed: AtomicU64,
+}
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            elements: std::array::from_fn(|_| AtomicU64::new(0)),
+            state_entries: std::array::from_fn(|_| AtomicU64::new(0)),
+            resets: AtomicU64::new(0),
+            max_depth: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+        }
+    }
+
+    pub fn elements(&self, element: &SuumoElement) -> u64 {
+        self.elements[element.index()].load(Ordering::Relaxed)
+    }
+
+    pub fn state_entries(&self, state: &SuumoState) -> u64 {
+        self.state_entries[state.index()].load(Ordering::Relaxed)
+    }
+
+    pub fn resets(&self) -> u64 {
+        self.resets.load(Ordering::Relaxed)
+    }
+
+    pub fn max_depth(&self) -> u64 {
+        self.max_depth.load(Ordering::Relaxed)
+    }
+
+    pub fn completed(&self) -> u64 {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    // Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+
+        text.push_str("# HELP suumo_elements_total Elements generated.\n");
+        text.push_str("# TYPE suumo_elements_total counter\n");
+        for element in SuumoElement::ALL.iter() {
+            let _ = writeln!(
+                text,
+                "suumo_elements_total{{element=\"{:?}\"}} {}",
+                element,
+                self.elements(element)
+            );
+        }
+
+        text.push_str("# HELP suumo_state_entries_total Times each state was entered.\n");
+        text.push_str("# TYPE suumo_state_entries_total counter\n");
+        for state in SuumoState::ALL.iter() {
+            let _ = writeln!(
+                text,
+                "suumo_state_entries_total{{state=\"{:?}\"}} {}",
+                state,
+                self.state_entries(state)
+            );
+        }
+
+        for (name, kind, help, value) in [
+            (
+                "suumo_resets_total",
+                "counter",
+                "Partial matches broken by an element.",
+                self.resets(),
+            ),
+            (
+                "suumo_max_depth",
+                "gauge",
+                "Most elements of the jingle matched in a row.",
+                self.max_depth(),
+            ),
+            (
+                "suumo_completed_runs_total",
+                "counter",
+                "Runs that reached the jingle.",
+                self.completed(),
+            ),
+        ] {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} {}", name, kind);
+            let _ = writeln!(text, "{} {}", name, value);
+        }
+
+        text
+    }
+}
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Counts the transitions of a run into shared `Metrics`.
+#[derive(Debug, Clone)]
+pub struct MetricsObserver {
+    metrics: Arc<Metrics>,
+}
+impl MetricsObserver {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self { metrics }
+    }
+}
+impl TransitionObserver for MetricsObserver {
+    fn on_step(&mut self, _step: u64, transition: &Transition) -> io::Result<()> {
+        self.metrics.elements[transition.element.index()].fetch_add(1, Ordering::Relaxed);
+        self.metrics.state_entries[transition.to.index()].fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn on_reset(&mut self, _step: u64, _transition: &Transition) -> io::Result<()> {
+        self.metrics.resets.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn on_new_max_depth(&mut self, _step: u64, depth: usize) -> io::Result<()> {
+        self.metrics
+            .max_depth
+            .fetch_max(depth as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn on_terminate(&mut self, _step: u64) -> io::Result<()> {
+        self.metrics.completed.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// Connections served at once. Further ones are closed right away.
+const MAX_CONNECTIONS: usize = 8;
+// Bytes of the request line and headers read before answering.
+const MAX_REQUEST_LEN: u64 = 8192;
+
+// Serves `GET /metrics` on `addr` from a background thread and returns the
+// address it is listening on. Nothing restricts who can connect, so bind to a
+// loopback address such as 127.0.0.1 unless the metrics should be
+// reachable from other machines.
+pub fn serve(addr: impl ToSocketAddrs, metrics: Arc<Metrics>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+
+    thread::spawn(move || {
+        let active = Arc::new(AtomicUsize::new(0));
+        for stream in listener.incoming().flatten() {
+            if active.fetch_add(1, Ordering::AcqRel) >= MAX_CONNECTIONS {
+                active.fetch_sub(1, Ordering::AcqRel);
+                continue;
+            }
+            // Every connection gets its own thread, so a slow client only
+            // delays its own response, and the timeouts bound how long it
+            // holds one of the `MAX_CONNECTIONS`.
+            let metrics = metrics.clone();
+            let active = active.clone();
+            thread::spawn(move || {
+                let _ = respond(stream, &metrics);
+                active.fetch_sub(1, Ordering::AcqRel);
+            });
+        }
+    });
+
+    Ok(local_addr)
+}
+
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut reader = BufReader::new(stream.try_clone()?.take(MAX_REQUEST_LEN));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers.
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.render()),
+        (Some("GET"), _) => ("404 Not Found", "Not Found\n".to_string()),
+        _ => ("405 Method Not Allowed", "Method Not Allowed\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generator::ElementGenerator, observer::run_observed};
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::io::Read;
+
+    #[test]
+    fn metrics_observer_counts() {
+        let metrics = Arc::new(Metrics::new());
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        let mut state = SuumoState::new();
+        let mut history = crate::history::SuumoHistory::new(100_000);
+
+        run_observed(
+            &mut state,
+            &mut generator,
+            Some(100_000),
+            &mut vec![
+                &mut MetricsObserver::new(metrics.clone()) as &mut dyn TransitionObserver,
+                &mut history,
+            ],
+        )
+        .unwrap();
+
+        let elements: u64 = SuumoElement::ALL.iter().map(|e| metrics.elements(e)).sum();
+        let entries: u64 = SuumoState::ALL
+            .iter()
+            .map(|s| metrics.state_entries(s))
+            .sum();
+        assert_eq!(elements, 100_000);
+        assert_eq!(entries, 100_000);
+        assert_eq!(
+            metrics.resets(),
+            history.transitions().filter(|t| t.is_reset()).count() as u64
+        );
+        assert_eq!(
+            metrics.max_depth(),
+            history.transitions().map(|t| t.to.index()).max().unwrap() as u64
+        );
+        assert_eq!(metrics.completed(), 0);
+    }
+
+    #[test]
+    fn render_metrics() {
+        let metrics = Arc::new(Metrics::new());
+        let mut observer = MetricsObserver::new(metrics.clone());
+        let transition = Transition {
+            from: SuumoState::S0,
+            element: SuumoElement::ASuumo,
+            to: SuumoState::S1,
+        };
+        observer.on_step(1, &transition).unwrap();
+        observer.on_new_max_depth(1, 1).unwrap();
+        observer.on_terminate(19).unwrap();
+
+        let text = metrics.render();
+        assert!(text.contains("suumo_elements_total{element=\"ASuumo\"} 1\n"));
+        assert!(text.contains("suumo_elements_total{element=\"Dan\"} 0\n"));
+        assert!(text.contains("suumo_state_entries_total{state=\"S1\"} 1\n"));
+        assert!(text.contains("# TYPE suumo_max_depth gauge\nsuumo_max_depth 1\n"));
+        assert!(text.contains("suumo_completed_runs_total 1\n"));
+        assert_eq!(
+            text.lines().filter(|l| !l.starts_with('#')).count(),
+            7 + 20 + 3
+        );
+    }
+
+    #[test]
+    fn serve_metrics() {
+        let metrics = Arc::new(Metrics::new());
+        metrics.resets.fetch_add(3, Ordering::Relaxed);
+        let addr = serve("127.0.0.1:0", metrics).unwrap();
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(2)))
+                .unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        // A client that never sends its request doesn't hold up the others.
+        let _idle = TcpStream::connect(addr).unwrap();
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("suumo_completed_runs_total 0\n"));
+        assert!(response.contains("\nsuumo_resets_total 3\n"));
+        assert!(get("/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn serve_limits_connections() {
+        let addr = serve("127.0.0.1:0", Arc::new(Metrics::new())).unwrap();
+        let get = || -> io::Result<String> {
+            let mut stream = TcpStream::connect(addr)?;
+            stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+            stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n")?;
+            let mut response = String::new();
+            stream.read_to_string(&mut response)?;
+            Ok(response)
+        };
+
+        let idle: Vec<_> = (0..MAX_CONNECTIONS)
+            .map(|_| TcpStream::connect(addr).unwrap())
+            .collect();
+        // Waits for the listener to take all of them.
+        thread::sleep(Duration::from_millis(200));
+        assert!(get().map_or(true, |response| response.is_empty()));
+
+        drop(idle);
+        let served = (0..50).any(|_| {
+            thread::sleep(Duration::from_millis(100));
+            get().is_ok_and(|response| response.starts_with("HTTP/1.1 200 OK\r\n"))
+        });
+        assert!(served);
+
+        // Endless headers stop being read after `MAX_REQUEST_LEN` bytes, so
+        // the server answers or closes before the client's read times out.
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n").unwrap();
+        let header = format!("X: {}\r\n", "x".repeat(1000));
+        for _ in 0..MAX_REQUEST_LEN as usize / header.len() + 1 {
+            if stream.write_all(header.as_bytes()).is_err() {
+                break;
+            }
+        }
+        let read = stream.read_to_string(&mut String::new());
+        assert!(
+            read.as_ref().map_or_else(
+                |e| !matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ),
+                |_| true
+            ),
+            "{:?}",
+            read
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use rand::RngCore;
+
+use crate::{
+    generator::{ElementGenerator, RunProgress},
+    history::Transition,
+    SuumoElement, SuumoState,
+};
+
+// Hooks called by `run_observed`. Steps are numbered from 1 and every hook
+// defaults to doing nothing.
+pub trait TransitionObserver {
+    fn on_step(&mut self, _step: u64, _transition: &Transition) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Called after `on_step` when the step broke a partial match.
+    fn on_reset(&mut self, _step: u64, _transition: &Transition) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Called after `on_step` when the run matched more of the jingle than
+    // ever before.
+    fn on_new_max_depth(&mut self, _step: u64, _depth: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Called after `on_step` when the run reached `SuumoState::S19`.
+    fn on_terminate(&mut self, _step: u64) -> io::Result<()> {
+        Ok(())
+    }
+}
+// Ignores everything.
+impl TransitionObserver for () {}
+impl<O: TransitionObserver + ?Sized> TransitionObserver for &mut O {
+    fn on_step(&mut self, step: u64, transition: &Transition) -> io::Result<()> {
+        (**self).on_step(step, transition)
+    }
+
+    fn on_reset(&mut self, step: u64, transition: &Transition) -> io::Result<()> {
+        (**self).on_reset(step, transition)
+    }
+
+    fn on_new_max_depth(&mut self, step: u64, depth: usize) -> io::Result<()> {
+        (**self).on_new_max_depth(step, depth)
+    }
+
+    fn on_terminate(&mut self, step: u64) -> io::Result<()> {
+        (**self).on_terminate(step)
+    }
+}
+impl<O: TransitionObserver + ?Sized> TransitionObserver for Box<O> {
+    fn on_step(&mut self, step: u64, transition: &Transition) -> io::Result<()> {
+        (**self).on_step(step, transition)
+    }
+
+    fn on_reset(&mut self, step: u64, transition: &Transition) -> io::Result<()> {
+        (**self).on_reset(step, transition)
+    }
+
+    fn on_new_max_depth(&mut self, step: u64, depth: usize) -> io::Result<()> {
+        (**self).on_new_max_depth(step, depth)
+    }
+
+    fn on_terminate(&mut self, step: u64) -> io::Result<()> {
+        (**self).on_terminate(step)
+    }
+}
+impl<O: TransitionObserver> TransitionObserver for Vec<O> {
+    fn on_step(&mut self, step: u64, transition: &Transition) -> io::Result<()> {
+        self.iter_mut()
+            .try_for_each(|observer| observer.on_step(step, transition))
+    }
+
+    fn on_reset(&mut self, step: u64, transition: &Transition) -> io::Result<()> {
+        self.iter_mut()
+            .try_for_each(|observer| observer.on_reset(step, transition))
+    }
+
+    fn on_new_max_depth(&mut self, step: u64, depth: usize) -> io::Result<()> {
+        self.iter_mut()
+            .try_for_each(|observer| observer.on_new_max_depth(step, depth))
+    }
+
+    fn on_terminate(&mut self, step: u64) -> io::Result<()> {
+        self.iter_mut()
+            .try_for_each(|observer| observer.on_terminate(step))
+    }
+}
+
+// Writes every generated element.
+pub struct WriteObserver<W: Write> {
+    writer: W,
+}
+impl<W: Write> WriteObserver<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+impl<W: Write> TransitionObserver for WriteObserver<W> {
+    fn on_step(&mut self, _step: u64, transition: &Transition) -> io::Result<()> {
+        self.writer
+            .write_all(transition.element.as_str().as_bytes())
+    }
+}
+
+// Like `generator::write_run`, but reports every transition to `observer`
+// instead of writing the elements. Returns the number of steps taken.
+pub fn run_observed<R: RngCore, O: TransitionObserver + ?Sized>(
+    state: &mut SuumoState,
+    generator: &mut ElementGenerator<R>,
+    limit: Option<u64>,
+    observer: &mut O,
+) -> io::Result<u64> {
+    run_observed_until(state, generator, limit, observer, &AtomicBool::new(false))
+        .map(|progress| progress.steps)
+}
+
+// Like `run_observed`, but also stops before the next element once `stop` is
+// set.
+pub fn run_observed_until<R: RngCore, O: TransitionObserver + ?Sized>(
+    state: &mut SuumoState,
+    generator: &mut ElementGenerator<R>,
+    limit: Option<u64>,
+    observer: &mut O,
+    stop: &AtomicBool,
+) -> io::Result<RunProgress> {
+    let limit = limit.unwrap_or(u64::MAX);
+    let mut max_depth = state.index();
+    let mut step = 0;
+    while *state != SuumoState::S19 && step < limit && !stop.load(Ordering::Relaxed) {
+        let element = generator.next_index();
+        let to = SuumoState::TRANSITIONS[state.index()][element] as usize;
+        let transition = Transition {
+            from: state.clone(),
+            element: SuumoElement::ALL[element].clone(),
+            to: SuumoState::ALL[to].clone(),
+        };
+        *state = transition.to.clone();
+        step += 1;
+
+        observer.on_step(step, &transition)?;
+        if transition.is_reset() {
+            observer.on_reset(step, &transition)?;
+        }
+        if to > max_depth {
+            max_depth = to;
+            observer.on_new_max_depth(step, to)?;
+        }
+        if to == SuumoState::S19.index() {
+            observer.on_terminate(step)?;
+        }
+    }
+
+    Ok(RunProgress {
+        steps: step,
+        deepest: max_depth,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{
+        rngs::{mock::StepRng, StdRng},
+        SeedableRng,
+    };
+
+    #[derive(Default)]
+    struct Recorder {
+        steps: u64,
+        resets: Vec<u64>,
+        depths: Vec<(u64, usize)>,
+        terminated: Option<u64>,
+    }
+    impl TransitionObserver for Recorder {
+        fn on_step(&mut self, step: u64, _transition: &Transition) -> io::Result<()> {
+            assert_eq!(step, self.steps + 1);
+            self.steps = step;
+            Ok(())
+        }
+
+        fn on_reset(&mut self, step: u64, transition: &Transition) -> io::Result<()> {
+            assert!(transition.is_reset());
+            self.resets.push(step);
+            Ok(())
+        }
+
+        fn on_new_max_depth(&mut self, step: u64, depth: usize) -> io::Result<()> {
+            self.depths.push((step, depth));
+            Ok(())
+        }
+
+        fn on_terminate(&mut self, step: u64) -> io::Result<()> {
+            self.terminated = Some(step);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn observe_jingle() {
+        let word = SuumoElement::JINGLE
+            .iter()
+            .enumerate()
+            .fold(0, |word, (i, element)| {
+                word | (element.index() as u64) << (3 * i)
+            });
+        let mut generator = ElementGenerator::new(StepRng::new(word, 0));
+        let mut state = SuumoState::new();
+        let mut observers = vec![
+            Box::new(Recorder::default()) as Box<dyn TransitionObserver>,
+            Box::new(WriteObserver::new(Vec::new())),
+        ];
+        let mut recorder = Recorder::default();
+        let mut writer = WriteObserver::new(Vec::new());
+
+        let steps = run_observed(
+            &mut state,
+            &mut generator,
+            None,
+            &mut vec![
+                &mut recorder as &mut dyn TransitionObserver,
+                &mut writer,
+                &mut observers,
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(steps, 19);
+        assert_eq!(state, SuumoState::S19);
+        assert_eq!(recorder.steps, 19);
+        assert!(recorder.resets.is_empty());
+        assert_eq!(
+            recorder.depths,
+            (1..=19).map(|i| (i as u64, i)).collect::<Vec<_>>()
+        );
+        assert_eq!(recorder.terminated, Some(19));
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            SuumoElement::JINGLE
+                .iter()
+                .map(|e| e.as_str())
+                .collect::<String>()
+        );
+
+        // A finished run does not step any further.
+        let mut recorder = Recorder::default();
+        let steps = run_observed(&mut state, &mut generator, None, &mut recorder).unwrap();
+        assert_eq!(steps, 0);
+        assert_eq!(recorder.terminated, None);
+    }
+
+    #[test]
+    fn observe_random_run() {
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        let mut state = SuumoState::new();
+        let mut recorder = Recorder::default();
+        let mut history = crate::history::SuumoHistory::new(1_000_000);
+
+        let steps = run_observed(
+            &mut state,
+            &mut generator,
+            Some(100_000),
+            &mut vec![&mut recorder as &mut dyn TransitionObserver, &mut history],
+        )
+        .unwrap();
+
+        assert_eq!(steps, 100_000);
+        assert_eq!(recorder.steps, 100_000);
+        assert_eq!(recorder.terminated, None);
+        assert_eq!(history.state(), &state);
+        assert_eq!(history.steps(), 100_000);
+        let resets: Vec<u64> = history
+            .transitions()
+            .zip(1..)
+            .filter(|(t, _)| t.is_reset())
+            .map(|(_, step)| step)
+            .collect();
+        assert_eq!(recorder.resets, resets);
+        assert!(recorder.depths.windows(2).all(|w| w[0].1 < w[1].1));
+    }
+
+    #[test]
+    fn observer_errors_stop_the_run() {
+        struct Failing;
+        impl TransitionObserver for Failing {
+            fn on_step(&mut self, step: u64, _transition: &Transition) -> io::Result<()> {
+                if step == 3 {
+                    return Err(io::ErrorKind::BrokenPipe.into());
+                }
+                Ok(())
+            }
+        }
+
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        let result = run_observed(&mut SuumoState::new(), &mut generator, None, &mut Failing);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn stopped_run() {
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        let mut state = SuumoState::new();
+        let mut recorder = Recorder::default();
+
+        let progress = run_observed_until(
+            &mut state,
+            &mut generator,
+            Some(1000),
+            &mut recorder,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        assert_eq!(progress.steps, 1000);
+        assert_eq!(
+            Some(progress.deepest),
+            recorder.depths.last().map(|&(_, depth)| depth)
+        );
+
+        let progress = run_observed_until(
+            &mut state,
+            &mut generator,
+            None,
+            &mut recorder,
+            &AtomicBool::new(true),
+        )
+        .unwrap();
+        assert_eq!(progress.steps, 0);
+        assert_eq!(recorder.steps, 1000);
+    }
+}
@@ -0,0 +1,421 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+mod options;
+
+pub use options::{parse_size, Compression};
+
+impl Compression {
+    fn extension(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Gzip => Some("gz"),
+            Self::Zstd => Some("zst"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputChunk {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub compressed_bytes: u64,
+}
+
+enum ChunkWriter {
+    Plain(BufWriter<File>),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<BufWriter<File>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+impl ChunkWriter {
+    fn create(path: &Path, compression: Compression) -> io::Result<Self> {
+        let file = BufWriter::with_capacity(1 << 20, File::create(path)?);
+        match compression {
+            Compression::None => Ok(Self::Plain(file)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Self::Gzip(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            ))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Self::Zstd(zstd::Encoder::new(file, 0)?)),
+            #[allow(unreachable_patterns)]
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "compression is not enabled in this build",
+            )),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Plain(w) => w.write_all(buf),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(w) => w.write_all(buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => w.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(w) => w.flush(),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => w.flush(),
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            Self::Plain(w) => w,
+            #[cfg(feature = "gzip")]
+            Self::Gzip(w) => w.finish()?,
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => w.finish()?,
+        };
+        file.flush()
+    }
+}
+
+// Writes output to `path`, optionally splitting it into chunk files
+// (`path.00000`, `path.00001`, ...) of at most `max_size` uncompressed bytes,
+// and compressing every chunk. Chunks never exceed `max_size`: writes are split
+// between UTF-8 characters, and only inside one when `max_size` is smaller than
+// the character. The list of finished chunks is kept up to date in
+// `path.manifest`.
+pub struct RotatingWriter {
+    path: PathBuf,
+    max_size: Option<u64>,
+    compression: Compression,
+    current: Option<(PathBuf, ChunkWriter)>,
+    written: u64,
+    chunks: Vec<OutputChunk>,
+}
+impl RotatingWriter {
+    pub fn new(
+        path: impl Into<PathBuf>,
+        max_size: Option<u64>,
+        compression: Compression,
+    ) -> io::Result<Self> {
+        let mut writer = Self {
+            path: path.into(),
+            max_size,
+            compression,
+            current: None,
+            written: 0,
+            chunks: Vec::new(),
+        };
+        writer.open_chunk()?;
+
+        Ok(writer)
+    }
+
+    pub fn manifest_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".manifest");
+        path.into()
+    }
+
+    pub fn chunks(&self) -> &[OutputChunk] {
+        &self.chunks
+    }
+
+    pub fn finish(mut self) -> io::Result<Vec<OutputChunk>> {
+        self.close_chunk()?;
+
+        Ok(self.chunks)
+    }
+
+    fn chunk_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        if self.max_size.is_some() {
+            path.push(format!(".{:05}", index));
+        }
+        if let Some(extension) = self.compression.extension() {
+            path.push(format!(".{}", extension));
+        }
+        path.into()
+    }
+
+    fn open_chunk(&mut self) -> io::Result<()> {
+        let path = self.chunk_path(self.chunks.len());
+        let writer = ChunkWriter::create(&path, self.compression)?;
+        self.current = Some((path, writer));
+        self.written = 0;
+
+        Ok(())
+    }
+
+    fn close_chunk(&mut self) -> io::Result<()> {
+        let Some((path, writer)) = self.current.take() else {
+            return Ok(());
+        };
+        writer.finish()?;
+        let compressed_bytes = fs::metadata(&path)?.len();
+        self.chunks.push(OutputChunk {
+            path,
+            bytes: self.written,
+            compressed_bytes,
+        });
+
+        self.write_manifest()
+    }
+
+    fn write_manifest(&self) -> io::Result<()> {
+        let mut manifest = String::from("file\tbytes\tcompressed_bytes\n");
+        for chunk in self.chunks.iter() {
+            let name = chunk.path.file_name().unwrap_or_default().to_string_lossy();
+            manifest.push_str(&format!(
+                "{}\t{}\t{}\n",
+                name, chunk.bytes, chunk.compressed_bytes
+            ));
+        }
+
+        // Replace the manifest atomically so an interrupted run never leaves a
+        // truncated one behind.
+        let mut tmp = self.manifest_path().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, manifest)?;
+        fs::rename(&tmp, self.manifest_path())
+    }
+}
+impl Write for RotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut len = buf.len();
+        if let Some(max_size) = self.max_size {
+            len = fitting_len(buf, max_size.saturating_sub(self.written));
+            if len == 0 && self.written > 0 {
+                self.close_chunk()?;
+                self.open_chunk()?;
+                len = fitting_len(buf, max_size);
+            }
+            // A character larger than the whole chunk has to be split.
+            if len == 0 {
+                len = max_size as usize;
+            }
+        }
+
+        let (_, writer) = self.current.as_mut().ok_or(io::ErrorKind::BrokenPipe)?;
+        writer.write_all(&buf[..len])?;
+        self.written += len as u64;
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current.as_mut() {
+            Some((_, writer)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+// The longest prefix of `buf` that fits in `capacity` bytes without splitting
+// a UTF-8 character, so that every chunk is valid UTF-8 on its own.
+fn fitting_len(buf: &[u8], capacity: u64) -> usize {
+    if buf.len() as u64 <= capacity {
+        return buf.len();
+    }
+    (1..=capacity as usize)
+        .rev()
+        .find(|&i| is_char_boundary(buf[i]))
+        .unwrap_or(0)
+}
+
+fn is_char_boundary(byte: u8) -> bool {
+    (byte as i8) >= -0x40
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("suumo-gen-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("512K"), Ok(512 << 10));
+        assert_eq!(parse_size("100M"), Ok(100 << 20));
+        assert_eq!(parse_size("100MiB"), Ok(100 << 20));
+        assert_eq!(parse_size("2g"), Ok(2 << 30));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("99999999999T").is_err());
+        assert_eq!(parse_size("10B"), Ok(10));
+        assert_eq!(parse_size("10kB"), Ok(10 << 10));
+        for junk in [
+            "10KIB", "10KBI", "10KIII", "10Kb", "10BB", "10I", "10iB", "10KI",
+        ] {
+            assert!(parse_size(junk).is_err(), "{}", junk);
+        }
+    }
+
+    #[test]
+    fn compression_from_str() {
+        assert_eq!("none".parse(), Ok(Compression::None));
+        assert!("lz4".parse::<Compression>().is_err());
+        assert_eq!(
+            "gzip".parse::<Compression>().is_ok(),
+            cfg!(feature = "gzip")
+        );
+        assert_eq!(
+            "zstd".parse::<Compression>().is_ok(),
+            cfg!(feature = "zstd")
+        );
+    }
+
+    #[test]
+    fn single_file() {
+        let dir = temp_dir("single");
+        let path = dir.join("out.txt");
+
+        let mut writer = RotatingWriter::new(&path, None, Compression::None).unwrap();
+        writer.write_all("ダン💥".as_bytes()).unwrap();
+        writer.write_all("シャーン🎶".as_bytes()).unwrap();
+        let chunks = writer.finish().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ダン💥シャーン🎶");
+        assert_eq!(
+            chunks,
+            vec![OutputChunk {
+                path: path.clone(),
+                bytes: 26,
+                compressed_bytes: 26
+            }]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("out.txt.manifest")).unwrap(),
+            "file\tbytes\tcompressed_bytes\nout.txt\t26\t26\n"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotation() {
+        let dir = temp_dir("rotation");
+        let path = dir.join("out.txt");
+
+        let mut writer = RotatingWriter::new(&path, Some(20), Compression::None).unwrap();
+        for _ in 0..5 {
+            writer.write_all("ダン💥".as_bytes()).unwrap();
+        }
+        assert_eq!(writer.chunks().len(), 2);
+        let chunks = writer.finish().unwrap();
+
+        let names: Vec<_> = chunks
+            .iter()
+            .map(|c| c.path.file_name().unwrap().to_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["out.txt.00000", "out.txt.00001", "out.txt.00002"]);
+        assert_eq!(
+            chunks.iter().map(|c| c.bytes).collect::<Vec<_>>(),
+            [20, 20, 10]
+        );
+        let content: String = chunks
+            .iter()
+            .map(|c| fs::read_to_string(&c.path).unwrap())
+            .collect();
+        assert_eq!(content, "ダン💥".repeat(5));
+        assert_eq!(
+            fs::read_to_string(dir.join("out.txt.manifest"))
+                .unwrap()
+                .lines()
+                .count(),
+            4
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotation_splits_writes() {
+        let dir = temp_dir("rotation-split");
+        let path = dir.join("out.txt");
+
+        let mut writer = RotatingWriter::new(&path, Some(7), Compression::None).unwrap();
+        writer.write_all("ダン💥シャーン🎶".as_bytes()).unwrap();
+        let chunks = writer.finish().unwrap();
+
+        let contents: Vec<_> = chunks
+            .iter()
+            .map(|c| fs::read_to_string(&c.path).unwrap())
+            .collect();
+        assert_eq!(contents, ["ダン", "💥シ", "ャー", "ン🎶"]);
+        assert_eq!(
+            chunks.iter().map(|c| c.bytes).collect::<Vec<_>>(),
+            [6, 7, 6, 7]
+        );
+
+        let mut writer = RotatingWriter::new(&path, Some(2), Compression::None).unwrap();
+        writer.write_all("ダン💥".as_bytes()).unwrap();
+        let chunks = writer.finish().unwrap();
+        assert!(chunks.iter().all(|c| c.bytes <= 2));
+        let contents: Vec<_> = chunks
+            .iter()
+            .flat_map(|c| fs::read(&c.path).unwrap())
+            .collect();
+        assert_eq!(contents, "ダン💥".as_bytes());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip() {
+        use std::io::Read;
+
+        let dir = temp_dir("gzip");
+        let path = dir.join("out.txt");
+
+        let mut writer = RotatingWriter::new(&path, None, Compression::Gzip).unwrap();
+        writer.write_all("スモ🌝".repeat(1000).as_bytes()).unwrap();
+        let chunks = writer.finish().unwrap();
+
+        assert_eq!(chunks[0].path, dir.join("out.txt.gz"));
+        assert_eq!(chunks[0].bytes, 10000);
+        assert!(chunks[0].compressed_bytes < 1000);
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(File::open(&chunks[0].path).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "スモ🌝".repeat(1000));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd() {
+        let dir = temp_dir("zstd");
+        let path = dir.join("out.txt");
+
+        let mut writer = RotatingWriter::new(&path, Some(5000), Compression::Zstd).unwrap();
+        for _ in 0..2 {
+            writer.write_all("スモ🌚".repeat(500).as_bytes()).unwrap();
+        }
+        let chunks = writer.finish().unwrap();
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].path, dir.join("out.txt.00001.zst"));
+        for chunk in chunks {
+            let content = zstd::decode_all(File::open(&chunk.path).unwrap()).unwrap();
+            assert_eq!(String::from_utf8(content).unwrap(), "スモ🌚".repeat(500));
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
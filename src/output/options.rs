@@ -0,0 +1,55 @@
+// Values of the output options of the command line.
+
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "none" => Ok(Self::None),
+            "gzip" | "gz" if cfg!(feature = "gzip") => Ok(Self::Gzip),
+            "zstd" | "zst" if cfg!(feature = "zstd") => Ok(Self::Zstd),
+            "gzip" | "gz" | "zstd" | "zst" => Err(format!(
+                "{} compression is not enabled in this build (enable the `{}` feature)",
+                s,
+                if s.starts_with('g') { "gzip" } else { "zstd" }
+            )),
+            _ => Err(format!("Invalid compression: {}", s)),
+        }
+    }
+}
+
+// Parses sizes like `1048576`, `512K`, `100MiB` or `2GB` (powers of 1024).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let (digits, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+    let prefix = unit
+        .strip_suffix("iB")
+        .or_else(|| unit.strip_suffix('B'))
+        .unwrap_or(unit);
+    let shift = match prefix {
+        "" if unit != "iB" => 0,
+        "K" | "k" => 10,
+        "M" | "m" => 20,
+        "G" | "g" => 30,
+        "T" | "t" => 40,
+        _ => return Err(format!("Invalid size: {}", s)),
+    };
+    let size = digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .filter(|&n| n > 0)
+        .ok_or(format!("Invalid size: {}", s))?;
+
+    Ok(size)
+}
@@ -0,0 +1,507 @@
+use std::{collections::VecDeque, fs, path::Path};
+
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use serde::Deserialize;
+
+use crate::SuumoElement;
+
+const SUUMO_ELEMENTS: [(&str, SuumoElement); 7] = [
+    ("ASuumo", SuumoElement::ASuumo),
+    ("Dan", SuumoElement::Dan),
+    ("Shaan", SuumoElement::Shaan),
+    ("SumoFullMoon", SuumoElement::SumoFullMoon),
+    ("SumoNewMoon", SuumoElement::SumoNewMoon),
+    ("SuuuumoUp", SuumoElement::SuuuumoUp),
+    ("SuuuumoDown", SuumoElement::SuuuumoDown),
+];
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PatternElement {
+    pub name: String,
+    pub text: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatternTargetFile {
+    sequence: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatternFile {
+    elements: Vec<PatternElement>,
+    targets: Vec<PatternTargetFile>,
+}
+
+// An alphabet of weighted elements and the target sequences to hunt for.
+// The target sequences are compiled into a DFA (Aho-Corasick automaton) whose
+// state 0 is the start state, so for a single target state `n` means "the
+// last `n` elements match the first `n` elements of the target", exactly like
+// `SuumoState::S0` .. `SuumoState::S19`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    elements: Vec<PatternElement>,
+    targets: Vec<Vec<usize>>,
+    transitions: Vec<Vec<usize>>,
+    terminals: Vec<Option<usize>>,
+    distribution: WeightedIndex<u32>,
+}
+impl Pattern {
+    pub fn new(elements: Vec<PatternElement>, targets: Vec<Vec<String>>) -> Result<Self, String> {
+        if elements.is_empty() {
+            return Err("Pattern has no elements".to_string());
+        }
+        for (i, element) in elements.iter().enumerate() {
+            if element.name.is_empty() {
+                return Err(format!("Element #{} has an empty name", i + 1));
+            }
+            if element.text.is_empty() {
+                return Err(format!("Element {} has an empty text", element.name));
+            }
+            if elements[..i].iter().any(|e| e.name == element.name) {
+                return Err(format!("Duplicate element name: {}", element.name));
+            }
+        }
+        if targets.is_empty() {
+            return Err("Pattern has no targets".to_string());
+        }
+
+        let targets = targets
+            .into_iter()
+            .enumerate()
+            .map(|(i, target)| {
+                if target.is_empty() {
+                    return Err(format!("Target #{} is empty", i + 1));
+                }
+                target
+                    .iter()
+                    .map(|name| {
+                        elements.iter().position(|e| &e.name == name).ok_or(format!(
+                            "Unknown element in target #{}: {}",
+                            i + 1,
+                            name
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let distribution = WeightedIndex::new(elements.iter().map(|e| e.weight))
+            .map_err(|e| format!("Invalid weights: {}", e))?;
+        let (transitions, terminals) = build_automaton(elements.len(), &targets);
+
+        Ok(Self {
+            elements,
+            targets,
+            transitions,
+            terminals,
+            distribution,
+        })
+    }
+
+    pub fn suumo() -> Self {
+        let elements = SUUMO_ELEMENTS
+            .iter()
+            .map(|(name, element)| PatternElement {
+                name: name.to_string(),
+                text: element.to_string(),
+                weight: 1,
+            })
+            .collect();
+        let targets = vec![SuumoElement::JINGLE
+            .iter()
+            .map(|element| SUUMO_ELEMENTS[element.index()].0.to_string())
+            .collect()];
+
+        Self::new(elements, targets).unwrap()
+    }
+
+    pub fn from_toml_str(s: &str) -> Result<Self, String> {
+        let file: PatternFile =
+            toml::from_str(s).map_err(|e| format!("Invalid pattern file: {}", e))?;
+
+        Self::new(
+            file.elements,
+            file.targets.into_iter().map(|t| t.sequence).collect(),
+        )
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let s = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        Self::from_toml_str(&s)
+    }
+
+    pub fn elements(&self) -> &[PatternElement] {
+        &self.elements
+    }
+
+    pub fn targets(&self) -> &[Vec<usize>] {
+        &self.targets
+    }
+
+    pub fn state_count(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn transition(&self, state: usize, element: usize) -> usize {
+        self.transitions[state][element]
+    }
+
+    pub fn matched_target(&self, state: usize) -> Option<usize> {
+        self.terminals[state]
+    }
+
+    pub fn random_element(&self) -> usize {
+        self.random_element_with_rng(&mut rand::thread_rng())
+    }
+
+    pub fn random_element_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.distribution.sample(rng)
+    }
+}
+
+fn build_automaton(
+    alphabet: usize,
+    targets: &[Vec<usize>],
+) -> (Vec<Vec<usize>>, Vec<Option<usize>>) {
+    let mut children: Vec<Vec<Option<usize>>> = vec![vec![None; alphabet]];
+    let mut terminals = vec![None];
+    for (i, target) in targets.iter().enumerate() {
+        let mut node = 0;
+        for &element in target {
+            node = match children[node][element] {
+                Some(child) => child,
+                None => {
+                    children.push(vec![None; alphabet]);
+                    terminals.push(None);
+                    let child = children.len() - 1;
+                    children[node][element] = Some(child);
+                    child
+                }
+            };
+        }
+        terminals[node].get_or_insert(i);
+    }
+
+    let mut transitions = vec![vec![0; alphabet]; children.len()];
+    let mut fail = vec![0; children.len()];
+    let mut queue = VecDeque::from([0]);
+    while let Some(node) = queue.pop_front() {
+        for element in 0..alphabet {
+            match children[node][element] {
+                Some(child) => {
+                    fail[child] = if node == 0 {
+                        0
+                    } else {
+                        transitions[fail[node]][element]
+                    };
+                    if terminals[child].is_none() {
+                        terminals[child] = terminals[fail[child]];
+                    }
+                    transitions[node][element] = child;
+                    queue.push_back(child);
+                }
+                None => {
+                    transitions[node][element] = if node == 0 {
+                        0
+                    } else {
+                        transitions[fail[node]][element]
+                    };
+                }
+            }
+        }
+    }
+
+    (transitions, terminals)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternState<'a> {
+    pattern: &'a Pattern,
+    state: usize,
+}
+impl<'a> PatternState<'a> {
+    pub fn new(pattern: &'a Pattern) -> Self {
+        Self { pattern, state: 0 }
+    }
+
+    pub fn state(&self) -> usize {
+        self.state
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.pattern.matched_target(self.state).is_some()
+    }
+
+    pub fn next_with_element(&mut self, next_element: usize) -> Option<usize> {
+        if self.is_terminal() {
+            return None;
+        }
+        self.state = self.pattern.transition(self.state, next_element);
+
+        Some(self.state)
+    }
+
+    // Draws the next element from `rng` and returns the new state and the
+    // element, or `None` once a target has been matched.
+    pub fn next_with_rng<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<(usize, usize)> {
+        let next_element = self.pattern.random_element_with_rng(rng);
+        self.next_with_element(next_element)
+            .map(|state| (state, next_element))
+    }
+
+    pub fn collect_to_string(&mut self) -> String {
+        let pattern = self.pattern;
+        let mut result = String::new();
+        for (_, element) in self {
+            result.push_str(&pattern.elements[element].text);
+        }
+
+        result
+    }
+
+    pub fn collect_to_string_nth(&mut self, n: usize) -> String {
+        let mut result = String::new();
+        for _ in 0..n {
+            if let Some((_, element)) = self.next() {
+                result.push_str(&self.pattern.elements[element].text);
+            } else {
+                break;
+            }
+        }
+
+        result
+    }
+}
+impl Iterator for PatternState<'_> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_rng(&mut rand::thread_rng())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        stats::{chi_squared, chi_squared_p_value, runs_test},
+        SuumoState,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn element(name: &str, text: &str, weight: u32) -> PatternElement {
+        PatternElement {
+            name: name.to_string(),
+            text: text.to_string(),
+            weight,
+        }
+    }
+
+    #[test]
+    fn suumo_pattern_matches_suumo_state() {
+        let pattern = Pattern::suumo();
+        assert_eq!(pattern.state_count(), SuumoState::ALL.len());
+
+        for (i, state) in SuumoState::ALL.iter().enumerate() {
+            for (j, (_, element)) in SUUMO_ELEMENTS.iter().enumerate() {
+                let mut suumo_state = state.clone();
+                let mut pattern_state = PatternState {
+                    pattern: &pattern,
+                    state: i,
+                };
+                let expected = suumo_state
+                    .next_with_suumo_element(element.clone())
+                    .map(|s| s.index());
+                assert_eq!(pattern_state.next_with_element(j), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn next_with_rng_is_reproducible() {
+        let pattern = Pattern::new(
+            vec![element("a", "A", 1), element("b", "B", 1)],
+            vec![vec!["a".to_string(), "a".to_string(), "a".to_string()]],
+        )
+        .unwrap();
+        let run = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut state = PatternState::new(&pattern);
+            std::iter::from_fn(|| state.next_with_rng(&mut rng)).collect::<Vec<_>>()
+        };
+
+        let steps = run(19);
+        assert_eq!(steps, run(19));
+        assert_eq!(
+            steps
+                .last()
+                .map(|&(state, _)| pattern.matched_target(state)),
+            Some(Some(0))
+        );
+        assert!(steps[steps.len() - 3..]
+            .iter()
+            .all(|&(_, element)| element == 0));
+    }
+
+    #[test]
+    fn suumo_pattern_file() {
+        let pattern =
+            Pattern::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/patterns/suumo.toml"))
+                .unwrap();
+
+        assert_eq!(pattern, Pattern::suumo());
+    }
+
+    #[test]
+    fn pattern_from_toml_str() {
+        let pattern = Pattern::from_toml_str(
+            r#"
+            [[elements]]
+            name = "a"
+            text = "A"
+            weight = 3
+
+            [[elements]]
+            name = "b"
+            text = "B"
+
+            [[targets]]
+            sequence = ["a", "b", "a"]
+
+            [[targets]]
+            sequence = ["b", "b"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            pattern.elements(),
+            &[element("a", "A", 3), element("b", "B", 1)]
+        );
+        assert_eq!(pattern.targets(), &[vec![0, 1, 0], vec![1, 1]]);
+
+        let mut pattern_state = PatternState::new(&pattern);
+        assert_eq!(pattern_state.next_with_element(0), Some(1));
+        assert_eq!(pattern_state.next_with_element(1), Some(2));
+        assert_eq!(pattern_state.next_with_element(1), Some(5));
+        assert!(pattern_state.is_terminal());
+        assert_eq!(pattern.matched_target(pattern_state.state()), Some(1));
+        assert_eq!(pattern_state.next_with_element(0), None);
+    }
+
+    #[test]
+    fn pattern_overlapping_target() {
+        let pattern = Pattern::new(
+            vec![element("a", "A", 1), element("b", "B", 1)],
+            vec![vec!["a".to_string(), "a".to_string(), "b".to_string()]],
+        )
+        .unwrap();
+
+        let mut pattern_state = PatternState::new(&pattern);
+        for element in [0, 0, 0, 0] {
+            pattern_state.next_with_element(element);
+        }
+        assert_eq!(pattern_state.state(), 2);
+        assert_eq!(pattern_state.next_with_element(1), Some(3));
+        assert!(pattern_state.is_terminal());
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        assert_eq!(
+            Pattern::new(vec![], vec![vec!["a".to_string()]]),
+            Err("Pattern has no elements".to_string())
+        );
+        assert_eq!(
+            Pattern::new(
+                vec![element("a", "A", 1), element("a", "B", 1)],
+                vec![vec!["a".to_string()]]
+            ),
+            Err("Duplicate element name: a".to_string())
+        );
+        assert_eq!(
+            Pattern::new(vec![element("a", "A", 1)], vec![]),
+            Err("Pattern has no targets".to_string())
+        );
+        assert_eq!(
+            Pattern::new(vec![element("a", "A", 1)], vec![vec![]]),
+            Err("Target #1 is empty".to_string())
+        );
+        assert_eq!(
+            Pattern::new(vec![element("a", "A", 1)], vec![vec!["b".to_string()]]),
+            Err("Unknown element in target #1: b".to_string())
+        );
+        assert!(Pattern::new(vec![element("a", "A", 0)], vec![vec!["a".to_string()]]).is_err());
+        assert!(Pattern::from_toml_str("elements = []").is_err());
+    }
+
+    #[test]
+    fn pattern_state_collect() {
+        let pattern = Pattern::new(
+            vec![element("a", "A", 1), element("b", "B", 0)],
+            vec![vec!["a".to_string(), "a".to_string()]],
+        )
+        .unwrap();
+
+        let mut pattern_state = PatternState::new(&pattern);
+        assert_eq!(pattern_state.collect_to_string(), "AA");
+        assert_eq!(pattern_state.collect_to_string_nth(3), "");
+
+        let mut pattern_state = PatternState::new(&pattern);
+        assert_eq!(pattern_state.collect_to_string_nth(1), "A");
+    }
+
+    #[test]
+    fn weighted_pattern_fairness() {
+        let pattern = Pattern::new(
+            vec![
+                element("a", "A", 1),
+                element("b", "B", 2),
+                element("c", "C", 3),
+                element("d", "D", 4),
+            ],
+            vec![vec!["a".to_string()]],
+        )
+        .unwrap();
+        let mut rng = StdRng::seed_from_u64(19);
+        let samples: Vec<_> = (0..500_000)
+            .map(|_| pattern.random_element_with_rng(&mut rng))
+            .collect();
+
+        let mut counts = [0; 4];
+        for &sample in &samples {
+            counts[sample] += 1;
+        }
+        let expected: Vec<_> = [1.0, 2.0, 3.0, 4.0]
+            .iter()
+            .map(|w| w / 10.0 * samples.len() as f64)
+            .collect();
+        let p = chi_squared_p_value(chi_squared(&counts, &expected), 3);
+        assert!(p > 0.001, "counts = {:?}, p = {}", counts, p);
+
+        for target in 0..4 {
+            let (z, p) = runs_test(samples.iter().map(|&s| s == target));
+            assert!(p > 0.001, "{}: z = {}, p = {}", target, z, p);
+        }
+
+        let suumo = Pattern::suumo();
+        let mut counts = [0; 7];
+        for _ in 0..700_000 {
+            counts[suumo.random_element_with_rng(&mut rng)] += 1;
+        }
+        let p = chi_squared_p_value(chi_squared(&counts, &[100_000.0; 7]), 6);
+        assert!(p > 0.001, "counts = {:?}, p = {}", counts, p);
+    }
+}
@@ -0,0 +1,367 @@
+// Python module for the `python` feature. Build it with `maturin develop`,
+// which picks up the features listed in pyproject.toml.
+
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyBytes, PyString},
+};
+use rand::rngs::StdRng;
+
+use crate::{
+    generator::{seeded_rng, ElementGenerator},
+    try_string_to_suumo_elements, SuumoElement, SuumoState,
+};
+
+const SIGNAL_CHECK_INTERVAL: u64 = 1 << 20;
+
+/// One of the seven elements. `int(element)` is its index.
+#[pyclass(name = "SuumoElement", eq, eq_int, frozen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PySuumoElement {
+    ASuumo,
+    Dan,
+    Shaan,
+    SumoFullMoon,
+    SumoNewMoon,
+    SuuuumoUp,
+    SuuuumoDown,
+}
+impl PySuumoElement {
+    const ALL: [PySuumoElement; 7] = [
+        Self::ASuumo,
+        Self::Dan,
+        Self::Shaan,
+        Self::SumoFullMoon,
+        Self::SumoNewMoon,
+        Self::SuuuumoUp,
+        Self::SuuuumoDown,
+    ];
+
+    fn element(&self) -> &'static SuumoElement {
+        &SuumoElement::ALL[*self as usize]
+    }
+}
+impl From<&SuumoElement> for PySuumoElement {
+    fn from(element: &SuumoElement) -> Self {
+        Self::ALL[element.index()]
+    }
+}
+#[pymethods]
+impl PySuumoElement {
+    #[staticmethod]
+    fn all() -> Vec<Self> {
+        Self::ALL.to_vec()
+    }
+
+    #[getter]
+    fn text(&self) -> &'static str {
+        self.element().as_str()
+    }
+
+    #[getter]
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    fn __str__(&self) -> &'static str {
+        self.element().as_str()
+    }
+}
+
+/// The jingle state machine. `index` is 19 once the whole jingle has been fed.
+#[pyclass(name = "SuumoState")]
+#[derive(Debug, Clone, Default)]
+pub struct PySuumoState {
+    state: SuumoState,
+}
+#[pymethods]
+impl PySuumoState {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    #[getter]
+    fn index(&self) -> usize {
+        self.state.index()
+    }
+
+    #[getter]
+    fn is_terminal(&self) -> bool {
+        self.state == SuumoState::S19
+    }
+
+    /// Feeds `element` and returns the index of the new state. The terminal
+    /// state only transitions to itself.
+    fn feed(&mut self, element: PySuumoElement) -> usize {
+        let next = SuumoState::TRANSITIONS[self.state.index()][element.index()];
+        self.state = SuumoState::ALL[next as usize].clone();
+
+        next as usize
+    }
+
+    fn reset(&mut self) {
+        self.state = SuumoState::new();
+    }
+
+    fn __repr__(&self) -> String {
+        format!("SuumoState({:?})", self.state)
+    }
+}
+
+/// Iterator over generated elements that stops once the jingle has been
+/// generated. The same seed always gives the same run.
+#[pyclass(name = "Generator")]
+#[derive(Debug, Clone)]
+pub struct PyGenerator {
+    generator: ElementGenerator<StdRng>,
+    state: SuumoState,
+    steps: u64,
+}
+#[pymethods]
+impl PyGenerator {
+    #[new]
+    #[pyo3(signature = (seed=None))]
+    fn new(seed: Option<u64>) -> Self {
+        Self {
+            generator: ElementGenerator::new(seeded_rng(seed)),
+            state: SuumoState::new(),
+            steps: 0,
+        }
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> Option<PySuumoElement> {
+        if self.state == SuumoState::S19 {
+            return None;
+        }
+        let element = self.generator.next_index();
+        let next = SuumoState::TRANSITIONS[self.state.index()][element];
+        self.state = SuumoState::ALL[next as usize].clone();
+        self.steps += 1;
+
+        Some(PySuumoElement::ALL[element])
+    }
+
+    /// Generates up to `limit` elements and returns their text.
+    fn text(&mut self, limit: usize) -> String {
+        let mut text = String::new();
+        for _ in 0..limit {
+            let Some(element) = self.__next__() else {
+                break;
+            };
+            text.push_str(element.text());
+        }
+
+        text
+    }
+
+    #[getter]
+    fn state(&self) -> usize {
+        self.state.index()
+    }
+
+    #[getter]
+    fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    #[getter]
+    fn is_terminal(&self) -> bool {
+        self.state == SuumoState::S19
+    }
+}
+
+/// Parses a string into elements. Raises `ValueError` if it is not a sequence
+/// of elements.
+#[pyfunction]
+fn parse(s: &str) -> PyResult<Vec<PySuumoElement>> {
+    let elements = try_string_to_suumo_elements(s.to_string()).map_err(PyValueError::new_err)?;
+
+    Ok(elements.iter().map(PySuumoElement::from).collect())
+}
+
+/// Runs `runs` independent runs and returns how many elements each one took
+/// to match the first `depth` elements of the jingle, as an `array.array` of
+/// unsigned 64-bit integers that `numpy.asarray` reads without copying. Runs
+/// that have not matched after `limit` elements stop and report `limit`.
+///
+/// A run takes about 7**depth elements on average, and tens of millions of
+/// elements take about a second. The full jingle (`depth=19`) needs about
+/// 1.1e16 elements per run, which never finishes in practice, so pass a
+/// `limit` or a smaller `depth`. Ctrl-C raises `KeyboardInterrupt`.
+#[pyfunction]
+#[pyo3(signature = (runs, seed=None, depth=19, limit=None))]
+fn simulate<'py>(
+    py: Python<'py>,
+    runs: usize,
+    seed: Option<u64>,
+    depth: usize,
+    limit: Option<u64>,
+) -> PyResult<Bound<'py, PyAny>> {
+    if !(1..=SuumoElement::JINGLE.len()).contains(&depth) {
+        return Err(PyValueError::new_err(format!(
+            "depth must be between 1 and {}",
+            SuumoElement::JINGLE.len()
+        )));
+    }
+
+    let lengths = py.detach(|| {
+        run_lengths(runs, seed, depth, limit, || {
+            Python::attach(|py| py.check_signals())
+        })
+    })?;
+    let bytes: Vec<u8> = lengths.iter().flat_map(|n| n.to_ne_bytes()).collect();
+
+    py.import("array")?
+        .getattr("array")?
+        .call1((PyString::new(py, "Q"), PyBytes::new(py, &bytes)))
+}
+
+// Calls `check` every `SIGNAL_CHECK_INTERVAL` elements and stops with its
+// error, so that `simulate` can be interrupted without holding the GIL.
+fn run_lengths(
+    runs: usize,
+    seed: Option<u64>,
+    depth: usize,
+    limit: Option<u64>,
+    mut check: impl FnMut() -> PyResult<()>,
+) -> PyResult<Vec<u64>> {
+    let mut generator = ElementGenerator::new(seeded_rng(seed));
+    let limit = limit.unwrap_or(u64::MAX);
+    let mut until_check = SIGNAL_CHECK_INTERVAL;
+    let mut lengths = Vec::with_capacity(runs);
+    for _ in 0..runs {
+        let mut current = 0;
+        let mut count = 0;
+        while (current as usize) < depth && count < limit {
+            current = SuumoState::TRANSITIONS[current as usize][generator.next_index()];
+            count += 1;
+
+            until_check -= 1;
+            if until_check == 0 {
+                check()?;
+                until_check = SIGNAL_CHECK_INTERVAL;
+            }
+        }
+        lengths.push(count);
+    }
+
+    Ok(lengths)
+}
+
+#[pymodule]
+fn suumo_gen(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySuumoElement>()?;
+    m.add_class::<PySuumoState>()?;
+    m.add_class::<PyGenerator>()?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(simulate, m)?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyDict;
+    use rand::SeedableRng;
+    use std::ffi::CString;
+
+    fn run(code: &str) {
+        Python::initialize();
+        Python::attach(|py| {
+            let globals = PyDict::new(py);
+            globals
+                .set_item("suumo_gen", pyo3::wrap_pymodule!(suumo_gen)(py))
+                .unwrap();
+            py.run(&CString::new(code).unwrap(), Some(&globals), None)
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn python_state_machine() {
+        run(r#"
+S = suumo_gen.SuumoElement
+state = suumo_gen.SuumoState()
+jingle = "あ❗️ スーモ❗️🌚ダン💥ダン💥ダン💥シャーン🎶スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚ス〜〜〜モ⤴🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝ス〜〜〜モ⤵🌞"
+for i, element in enumerate(suumo_gen.parse(jingle)):
+    assert state.feed(element) == i + 1
+assert state.is_terminal
+assert repr(state) == "SuumoState(S19)"
+assert state.feed(S.Dan) == 19
+state.reset()
+assert state.index == 0
+
+assert len(S.all()) == 7
+assert int(S.Dan) == S.Dan.index == 1 and S.Dan.text == "ダン💥" and str(S.Shaan) == "シャーン🎶"
+assert suumo_gen.parse("ダン💥シャーン🎶") == [S.Dan, S.Shaan]
+try:
+    suumo_gen.parse("ダン")
+    assert False
+except ValueError:
+    pass
+"#);
+    }
+
+    #[test]
+    fn python_generator() {
+        run(r#"
+a = suumo_gen.Generator(19)
+b = suumo_gen.Generator(seed=19)
+assert a.text(1000) == "".join(str(next(b)) for _ in range(1000))
+assert a.steps == 1000 and a.state == b.state and not a.is_terminal
+"#);
+    }
+
+    #[test]
+    fn python_simulate() {
+        run(r#"
+lengths = suumo_gen.simulate(100, seed=19, depth=2)
+assert lengths.typecode == "Q" and len(lengths) == 100
+assert memoryview(lengths).format == "Q"
+assert all(n >= 2 for n in lengths)
+assert list(lengths) == list(suumo_gen.simulate(100, seed=19, depth=2))
+assert list(suumo_gen.simulate(3, seed=19, limit=5)) == [5, 5, 5]
+try:
+    suumo_gen.simulate(1, depth=20)
+    assert False
+except ValueError:
+    pass
+"#);
+    }
+
+    #[test]
+    fn run_lengths_match_state_machine() {
+        let lengths = run_lengths(50, Some(7), 3, None, || Ok(())).unwrap();
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(7));
+        for length in lengths {
+            let mut state = SuumoState::new();
+            let mut count = 0;
+            while state.index() < 3 {
+                state.next_with_suumo_element(SuumoElement::ALL[generator.next_index()].clone());
+                count += 1;
+            }
+            assert_eq!(length, count);
+        }
+    }
+
+    #[test]
+    fn run_lengths_stop_on_signal() {
+        let mut checks = 0;
+        let result = run_lengths(1, Some(7), 19, None, || {
+            checks += 1;
+            match checks {
+                3 => Err(PyValueError::new_err("interrupted")),
+                _ => Ok(()),
+            }
+        });
+        assert!(result.is_err());
+        assert_eq!(checks, 3);
+    }
+}
@@ -0,0 +1,289 @@
+use num_bigint::BigUint;
+use num_rational::Ratio;
+
+use crate::{SuumoElement, SuumoState};
+
+// Yields the exact probability that a run has reached `SuumoState::S19`
+// within `n` elements, for `n = 0, 1, 2, ...`.
+#[derive(Debug, Clone)]
+pub struct TerminationCdf {
+    // `counts[s]` is the number of element sequences of the current length
+    // that end in state `s` without having terminated earlier.
+    counts: Vec<BigUint>,
+    terminated: BigUint,
+    total: BigUint,
+    started: bool,
+}
+impl TerminationCdf {
+    pub fn new() -> Self {
+        let mut counts = vec![BigUint::from(0u32); SuumoState::ALL.len()];
+        counts[SuumoState::S0.index()] = BigUint::from(1u32);
+
+        Self {
+            counts,
+            terminated: BigUint::from(0u32),
+            total: BigUint::from(1u32),
+            started: false,
+        }
+    }
+
+    fn step(&mut self) {
+        let terminal = SuumoState::S19.index();
+        let mut next_counts = vec![BigUint::from(0u32); SuumoState::ALL.len()];
+        for (state, count) in self.counts.iter().enumerate() {
+            if state == terminal {
+                continue;
+            }
+            for &next_state in &SuumoState::TRANSITIONS[state] {
+                next_counts[next_state as usize] += count;
+            }
+        }
+
+        self.terminated *= SuumoElement::ALL.len();
+        self.terminated += &next_counts[terminal];
+        self.total *= SuumoElement::ALL.len();
+        next_counts[terminal] = BigUint::from(0u32);
+        self.counts = next_counts;
+    }
+}
+impl Default for TerminationCdf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Iterator for TerminationCdf {
+    type Item = Ratio<BigUint>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.started {
+            self.step();
+        }
+        self.started = true;
+
+        Some(Ratio::new_raw(self.terminated.clone(), self.total.clone()))
+    }
+}
+
+// `cdf[n]` is the exact probability that a run has reached `SuumoState::S19`
+// within `n` elements, for `n` in `0..=steps`.
+pub fn termination_cdf(steps: usize) -> Vec<Ratio<BigUint>> {
+    TerminationCdf::new().take(steps + 1).collect()
+}
+
+// Exact expected number of elements until a run reaches `SuumoState::S19`:
+// the sum of `7^k` over every `k` for which the first `k` elements of the
+// jingle are also its last `k`, including the whole jingle.
+pub fn expected_termination_steps() -> BigUint {
+    let jingle = &SuumoElement::JINGLE;
+    (1..=jingle.len())
+        .filter(|&k| jingle[..k] == jingle[jingle.len() - k..])
+        .map(|k| BigUint::from(SuumoElement::ALL.len()).pow(k as u32))
+        .sum()
+}
+
+pub fn chi_squared(observed: &[u64], expected: &[f64]) -> f64 {
+    observed
+        .iter()
+        .zip(expected)
+        .map(|(&o, &e)| (o as f64 - e).powi(2) / e)
+        .sum()
+}
+
+pub fn chi_squared_uniform(observed: &[u64]) -> f64 {
+    let total: u64 = observed.iter().sum();
+    let expected = vec![total as f64 / observed.len() as f64; observed.len()];
+
+    chi_squared(observed, &expected)
+}
+
+// Upper tail probability of the chi-squared distribution with `df` degrees of
+// freedom, i.e. the p-value of a goodness-of-fit test.
+pub fn chi_squared_p_value(statistic: f64, df: usize) -> f64 {
+    gamma_q(df as f64 / 2.0, statistic / 2.0)
+}
+
+// Wald-Wolfowitz runs test for randomness of a two-valued sequence.
+// Returns the z score and the two-sided p-value.
+pub fn runs_test(sequence: impl IntoIterator<Item = bool>) -> (f64, f64) {
+    let mut counts = [0u64; 2];
+    let mut runs = 0u64;
+    let mut previous = None;
+    for value in sequence {
+        counts[value as usize] += 1;
+        if previous != Some(value) {
+            runs += 1;
+        }
+        previous = Some(value);
+    }
+
+    let (n1, n2) = (counts[0] as f64, counts[1] as f64);
+    let n = n1 + n2;
+    if n1 == 0.0 || n2 == 0.0 {
+        return (0.0, 1.0);
+    }
+    let mean = 2.0 * n1 * n2 / n + 1.0;
+    let variance = 2.0 * n1 * n2 * (2.0 * n1 * n2 - n) / (n * n * (n - 1.0));
+    if variance <= 0.0 {
+        return (0.0, 1.0);
+    }
+    let z = (runs as f64 - mean) / variance.sqrt();
+
+    (z, chi_squared_p_value(z * z, 1))
+}
+
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + i as f64 + 1.0)
+        });
+
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+// Regularized upper incomplete gamma function Q(a, x).
+fn gamma_q(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-15;
+    const TINY: f64 = 1e-300;
+
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut n = a;
+        while term.abs() > sum.abs() * EPSILON {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+        }
+
+        (1.0 - sum * prefix).max(0.0)
+    } else {
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / TINY;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..10_000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = b + an / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < EPSILON {
+                break;
+            }
+        }
+
+        prefix * h
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::ToPrimitive;
+
+    #[test]
+    fn termination_cdf_exact() {
+        let cdf = termination_cdf(21);
+        let total = |n: u32| BigUint::from(7u32).pow(n);
+
+        assert_eq!(cdf.len(), 22);
+        for (n, p) in cdf.iter().enumerate().take(19) {
+            assert_eq!(p, &Ratio::new(BigUint::from(0u32), total(n as u32)));
+        }
+        assert_eq!(cdf[19], Ratio::new(BigUint::from(1u32), total(19)));
+        assert_eq!(cdf[20], Ratio::new(BigUint::from(14u32), total(20)));
+        assert_eq!(cdf[21], Ratio::new(BigUint::from(147u32), total(21)));
+    }
+
+    #[test]
+    fn termination_cdf_monotonic() {
+        let cdf = termination_cdf(200);
+        let probabilities: Vec<f64> = cdf.iter().map(|p| p.to_f64().unwrap()).collect();
+
+        assert!(probabilities.windows(2).all(|w| w[0] <= w[1]));
+        assert!(probabilities[200] > 0.0 && probabilities[200] < 1.0);
+    }
+
+    #[test]
+    fn expected_steps() {
+        // The jingle starts with the only あ❗️ スーモ❗️🌚, so no proper prefix is
+        // also a suffix.
+        assert_eq!(expected_termination_steps(), BigUint::from(7u32).pow(19));
+        assert_eq!(
+            expected_termination_steps().to_u64(),
+            Some(11_398_895_185_373_143)
+        );
+    }
+
+    #[test]
+    fn chi_squared_statistic() {
+        assert_eq!(chi_squared_uniform(&[10, 10, 10]), 0.0);
+        assert_eq!(chi_squared_uniform(&[20, 10, 0]), 20.0);
+        assert_eq!(chi_squared(&[5, 15], &[10.0, 10.0]), 5.0);
+    }
+
+    #[test]
+    fn chi_squared_critical_values() {
+        for (statistic, df) in [
+            (3.841_459, 1),
+            (5.991_465, 2),
+            (12.591_587, 6),
+            (65.170_769, 48),
+        ] {
+            assert!((chi_squared_p_value(statistic, df) - 0.05).abs() < 1e-6);
+        }
+        assert!((chi_squared_p_value(16.811_894, 6) - 0.01).abs() < 1e-6);
+        assert_eq!(chi_squared_p_value(0.0, 6), 1.0);
+        assert!(chi_squared_p_value(1e6, 6) < 1e-100);
+    }
+
+    #[test]
+    fn runs_test_statistic() {
+        let alternating = (0..1000).map(|i| i % 2 == 0);
+        let (z, p) = runs_test(alternating);
+        assert!(z > 30.0 && p < 1e-100);
+
+        let clustered = (0..1000).map(|i| i < 500);
+        let (z, p) = runs_test(clustered);
+        assert!(z < -30.0 && p < 1e-100);
+
+        let (z, p) = runs_test([true, false, false, true, true, false, true, false]);
+        assert!((z - 0.763_762).abs() < 1e-6);
+        assert!((p - 0.445_009).abs() < 1e-6);
+
+        assert_eq!(runs_test([true; 10]), (0.0, 1.0));
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    fmt::{self, Display},
+    io::Read,
+};
+
+use crate::{transcript::read_elements, SuumoElement, SuumoState};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    // `steps` elements that did not take part in a reported attempt.
+    Noise {
+        steps: u64,
+    },
+    // An attempt that started at step `start` and matched the first `depth`
+    // elements of the jingle at step `step`. `reset` is false if the
+    // transcript ended before the attempt failed, or if it completed the
+    // jingle.
+    Attempt {
+        start: u64,
+        depth: usize,
+        step: u64,
+        reset: bool,
+    },
+}
+impl Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Noise { steps } => write!(f, "S0 noise ×{}", steps),
+            Self::Attempt {
+                depth, step, reset, ..
+            } => {
+                write!(f, "reached S{} at step {}", depth, step)?;
+                if *reset {
+                    write!(f, ", reset")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+// Replays a transcript through `SuumoState` and collapses it into the
+// attempts that matched at least `min_depth` elements of the jingle, with the
+// elements in between counted as noise. Steps are numbered from 1.
+#[derive(Debug, Clone)]
+pub struct Summarizer {
+    min_depth: usize,
+    state: SuumoState,
+    step: u64,
+    noise: u64,
+    // Start, depth and the step of the last progress of the current attempt.
+    // Any step that does not progress ends the attempt, so it always ends at
+    // its deepest state.
+    attempt: Option<(u64, usize, u64)>,
+    segments: Vec<Segment>,
+}
+impl Summarizer {
+    pub fn new(min_depth: usize) -> Self {
+        Self {
+            min_depth: min_depth.max(1),
+            state: SuumoState::new(),
+            step: 0,
+            noise: 0,
+            attempt: None,
+            segments: Vec::new(),
+        }
+    }
+
+    pub fn from_reader(reader: impl Read, min_depth: usize) -> Result<Vec<Segment>, String> {
+        let mut summarizer = Self::new(min_depth);
+        read_elements(reader, |element| {
+            summarizer.push(&element);
+            Ok(())
+        })?;
+
+        Ok(summarizer.finish())
+    }
+
+    pub fn push(&mut self, element: &SuumoElement) {
+        self.step += 1;
+        if self.state == SuumoState::S19 {
+            self.state = SuumoState::new();
+        }
+        let previous = self.state.index();
+        let depth = self
+            .state
+            .next_with_suumo_element(element.clone())
+            .unwrap()
+            .index();
+
+        match self.attempt.as_mut() {
+            Some((_, attempt_depth, attempt_step)) if depth == previous + 1 => {
+                *attempt_depth = depth;
+                *attempt_step = self.step;
+            }
+            _ => {
+                self.end_attempt(true);
+                if depth > 0 {
+                    self.attempt = Some((self.step, depth, self.step));
+                } else {
+                    self.noise += 1;
+                }
+            }
+        }
+
+        if depth == SuumoState::S19.index() {
+            self.end_attempt(false);
+        }
+    }
+
+    // Removes and returns the segments that are complete so far.
+    pub fn drain_segments(&mut self) -> std::vec::Drain<'_, Segment> {
+        self.segments.drain(..)
+    }
+
+    pub fn finish(mut self) -> Vec<Segment> {
+        self.end_attempt(false);
+        self.flush_noise();
+
+        self.segments
+    }
+
+    fn end_attempt(&mut self, reset: bool) {
+        let Some((start, depth, step)) = self.attempt.take() else {
+            return;
+        };
+        if depth < self.min_depth {
+            self.noise += step - start + 1;
+            return;
+        }
+
+        self.flush_noise();
+        self.segments.push(Segment::Attempt {
+            start,
+            depth,
+            step,
+            reset,
+        });
+    }
+
+    fn flush_noise(&mut self) {
+        if self.noise > 0 {
+            self.segments.push(Segment::Noise { steps: self.noise });
+            self.noise = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JINGLE: &str = "あ❗️ スーモ❗️🌚ダン💥ダン💥ダン💥シャーン🎶スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚ス〜〜〜モ⤴🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝ス〜〜〜モ⤵🌞";
+
+    fn summarize(transcript: &str, min_depth: usize) -> Vec<Segment> {
+        Summarizer::from_reader(transcript.as_bytes(), min_depth).unwrap()
+    }
+
+    #[test]
+    fn summarize_transcript() {
+        // Noise, a short attempt, noise, an attempt to S4 and the jingle.
+        let transcript = format!(
+            "ダン💥スモ🌝あ❗️ スーモ❗️🌚ダン💥スモ🌚あ❗️ スーモ❗️🌚ダン💥ダン💥ダン💥スモ🌝{}",
+            JINGLE
+        );
+        let segments = summarize(&transcript, 3);
+
+        assert_eq!(
+            segments,
+            [
+                Segment::Noise { steps: 5 },
+                Segment::Attempt {
+                    start: 6,
+                    depth: 4,
+                    step: 9,
+                    reset: true
+                },
+                Segment::Noise { steps: 1 },
+                Segment::Attempt {
+                    start: 11,
+                    depth: 19,
+                    step: 29,
+                    reset: false
+                },
+            ]
+        );
+        assert_eq!(
+            segments
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            "S0 noise ×5, reached S4 at step 9, reset, S0 noise ×1, reached S19 at step 29"
+        );
+        assert_eq!(
+            summarize(&transcript, 5),
+            [
+                Segment::Noise { steps: 10 },
+                Segment::Attempt {
+                    start: 11,
+                    depth: 19,
+                    step: 29,
+                    reset: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn summarize_restarting_attempt() {
+        // The second "あ❗️ スーモ❗️🌚" fails the first attempt and starts the next one.
+        let segments = summarize("あ❗️ スーモ❗️🌚ダン💥あ❗️ スーモ❗️🌚ダン💥ダン💥", 2);
+
+        assert_eq!(
+            segments,
+            [
+                Segment::Attempt {
+                    start: 1,
+                    depth: 2,
+                    step: 2,
+                    reset: true
+                },
+                Segment::Attempt {
+                    start: 3,
+                    depth: 3,
+                    step: 5,
+                    reset: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn summarize_counts_every_step() {
+        let transcript = format!("{}スモ🌝ダン💥{}ダン💥", JINGLE, JINGLE);
+        for min_depth in 1..=19 {
+            let mut summarizer = Summarizer::new(min_depth);
+            for element in crate::try_string_to_suumo_elements(transcript.clone()).unwrap() {
+                summarizer.push(&element);
+            }
+            let mut segments: Vec<_> = summarizer.drain_segments().collect();
+            segments.extend(summarizer.finish());
+
+            let steps: u64 = segments
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Noise { steps } => *steps,
+                    Segment::Attempt { start, step, .. } => step - start + 1,
+                })
+                .sum();
+            assert_eq!(steps, 41, "min_depth = {}", min_depth);
+            assert_eq!(
+                segments
+                    .iter()
+                    .filter(|s| matches!(s, Segment::Attempt { depth: 19, .. }))
+                    .count(),
+                2
+            );
+        }
+    }
+
+    #[test]
+    fn summarize_empty() {
+        assert_eq!(summarize("", 5), []);
+        assert_eq!(summarize("ダン💥", 5), [Segment::Noise { steps: 1 }]);
+    }
+}
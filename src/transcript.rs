@@ -0,0 +1,581 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use crate::{
+    history::Transition, observer::TransitionObserver, SuumoElement, SuumoElementParser, SuumoState,
+};
+
+// Binary transcript format (all integers little-endian):
+//
+//   header:  b"SUMO", version: u8, flags: u8 (bit 0: a seed follows),
+//            [seed: u64], alphabet size: u8, then for every element its
+//            UTF-8 text prefixed by its length as u8
+//   body:    blocks of `count: u32` followed by `count` 3-bit element
+//            indices packed LSB first into `ceil(count * 3 / 8)` bytes,
+//            terminated by a block with a count of 0
+//   trailer: final state index: u8, total element count: u64
+pub const MAGIC: &[u8; 4] = b"SUMO";
+pub const VERSION: u8 = 1;
+
+const FLAG_SEED: u8 = 1;
+const BITS: usize = 3;
+const BLOCK_SIZE: usize = 1 << 16;
+
+pub fn is_binary(prefix: &[u8]) -> bool {
+    prefix.starts_with(MAGIC)
+}
+
+pub struct TranscriptEncoder<W: Write> {
+    writer: W,
+    block: Vec<u8>,
+    state: u8,
+    count: u64,
+}
+impl<W: Write> TranscriptEncoder<W> {
+    pub fn new(mut writer: W, seed: Option<u64>) -> io::Result<Self> {
+        let mut header = MAGIC.to_vec();
+        header.push(VERSION);
+        match seed {
+            Some(seed) => {
+                header.push(FLAG_SEED);
+                header.extend(seed.to_le_bytes());
+            }
+            None => header.push(0),
+        }
+        header.push(SuumoElement::ALL.len() as u8);
+        for element in SuumoElement::ALL.iter() {
+            header.push(element.as_str().len() as u8);
+            header.extend(element.as_str().as_bytes());
+        }
+        writer.write_all(&header)?;
+
+        Ok(Self {
+            writer,
+            block: Vec::with_capacity(BLOCK_SIZE),
+            state: SuumoState::S0.index() as u8,
+            count: 0,
+        })
+    }
+
+    pub fn push(&mut self, element: &SuumoElement) -> io::Result<()> {
+        let index = element.index();
+        self.state = SuumoState::TRANSITIONS[self.state as usize][index];
+        self.count += 1;
+        self.block.push(index as u8);
+        if self.block.len() == BLOCK_SIZE {
+            self.write_block()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_block()?;
+        self.writer.write_all(&0u32.to_le_bytes())?;
+        self.writer.write_all(&[self.state])?;
+        self.writer.write_all(&self.count.to_le_bytes())?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+
+        let mut bytes = vec![0u8; (self.block.len() * BITS).div_ceil(8)];
+        for (i, &index) in self.block.iter().enumerate() {
+            let bit = i * BITS;
+            let value = (index as u16) << (bit % 8);
+            bytes[bit / 8] |= value as u8;
+            if let Some(byte) = bytes.get_mut(bit / 8 + 1) {
+                *byte |= (value >> 8) as u8;
+            }
+        }
+        self.writer
+            .write_all(&(self.block.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&bytes)?;
+        self.block.clear();
+
+        Ok(())
+    }
+}
+impl<W: Write> TransitionObserver for TranscriptEncoder<W> {
+    fn on_step(&mut self, _step: u64, transition: &Transition) -> io::Result<()> {
+        self.push(&transition.element)
+    }
+}
+
+pub struct TranscriptDecoder<R: Read> {
+    reader: R,
+    seed: Option<u64>,
+    block: Vec<u8>,
+    position: usize,
+    state: u8,
+    count: u64,
+    finished: bool,
+}
+impl<R: Read> TranscriptDecoder<R> {
+    pub fn new(mut reader: R) -> Result<Self, String> {
+        let mut magic = [0; 4];
+        read_exact(&mut reader, &mut magic)?;
+        if &magic != MAGIC {
+            return Err("Not a binary transcript".to_string());
+        }
+        let [version, flags] = read_array(&mut reader)?;
+        if version != VERSION {
+            return Err(format!("Unsupported transcript version: {}", version));
+        }
+        let seed = match flags {
+            0 => None,
+            FLAG_SEED => Some(u64::from_le_bytes(read_array(&mut reader)?)),
+            _ => return Err(format!("Invalid transcript flags: {:#04x}", flags)),
+        };
+
+        let [size] = read_array(&mut reader)?;
+        if size as usize != SuumoElement::ALL.len() {
+            return Err("Unsupported transcript alphabet".to_string());
+        }
+        for element in SuumoElement::ALL.iter() {
+            let [len] = read_array(&mut reader)?;
+            let mut text = vec![0; len as usize];
+            read_exact(&mut reader, &mut text)?;
+            if text != element.as_str().as_bytes() {
+                return Err("Unsupported transcript alphabet".to_string());
+            }
+        }
+
+        Ok(Self {
+            reader,
+            seed,
+            block: Vec::new(),
+            position: 0,
+            state: SuumoState::S0.index() as u8,
+            count: 0,
+            finished: false,
+        })
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn state(&self) -> SuumoState {
+        SuumoState::ALL[self.state as usize].clone()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.count
+    }
+
+    // Returns the next element, or `None` once the trailer has been read and
+    // checked against the decoded elements.
+    pub fn next_element(&mut self) -> Result<Option<SuumoElement>, String> {
+        if self.position == self.block.len() && (self.finished || !self.read_block()?) {
+            return Ok(None);
+        }
+
+        let index = self.block[self.position] as usize;
+        self.position += 1;
+        self.state = SuumoState::TRANSITIONS[self.state as usize][index];
+        self.count += 1;
+
+        Ok(Some(SuumoElement::ALL[index].clone()))
+    }
+
+    fn read_block(&mut self) -> Result<bool, String> {
+        let len = u32::from_le_bytes(read_array(&mut self.reader)?) as usize;
+        if len == 0 {
+            self.finished = true;
+            self.read_trailer()?;
+            return Ok(false);
+        }
+        if len > BLOCK_SIZE {
+            return Err(format!("Invalid transcript block size: {}", len));
+        }
+
+        let mut bytes = vec![0; (len * BITS).div_ceil(8)];
+        read_exact(&mut self.reader, &mut bytes)?;
+        self.block.clear();
+        self.position = 0;
+        for i in 0..len {
+            let bit = i * BITS;
+            let low = bytes[bit / 8] as u16;
+            let high = bytes.get(bit / 8 + 1).copied().unwrap_or(0) as u16;
+            let index = (((high << 8) | low) >> (bit % 8)) as u8 & 0b111;
+            if index as usize >= SuumoElement::ALL.len() {
+                return Err(format!("Invalid element index: {}", index));
+            }
+            self.block.push(index);
+        }
+
+        Ok(true)
+    }
+
+    fn read_trailer(&mut self) -> Result<(), String> {
+        let [state] = read_array(&mut self.reader)?;
+        let count = u64::from_le_bytes(read_array(&mut self.reader)?);
+        if state != self.state || count != self.count {
+            return Err(format!(
+                "Corrupted transcript: trailer says {} elements ending in S{}, but {} elements ending in S{} were decoded",
+                count, state, self.count, self.state
+            ));
+        }
+
+        Ok(())
+    }
+}
+impl<R: Read> Iterator for TranscriptDecoder<R> {
+    type Item = Result<SuumoElement, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_element().transpose()
+    }
+}
+
+// Peeks at the start of `reader` to tell whether it holds a binary transcript,
+// and returns a reader that still yields the whole input.
+pub fn detect<R: Read>(mut reader: R) -> Result<(bool, impl Read), String> {
+    let mut prefix = Vec::new();
+    reader
+        .by_ref()
+        .take(MAGIC.len() as u64)
+        .read_to_end(&mut prefix)
+        .map_err(|e| format!("Failed to read transcript: {}", e))?;
+
+    Ok((is_binary(&prefix), io::Cursor::new(prefix).chain(reader)))
+}
+
+// Calls `f` with every element of a text or binary transcript and returns the
+// number of elements.
+pub fn read_elements(
+    reader: impl Read,
+    mut f: impl FnMut(SuumoElement) -> Result<(), String>,
+) -> Result<u64, String> {
+    let (binary, reader) = detect(reader)?;
+    if !binary {
+        return read_text(reader, f);
+    }
+
+    let mut decoder = TranscriptDecoder::new(reader)?;
+    while let Some(element) = decoder.next_element()? {
+        f(element)?;
+    }
+
+    Ok(decoder.total())
+}
+
+// Parses a text transcript from `reader` and calls `f` with every element.
+// Line breaks between elements are ignored. Returns the number of elements.
+pub fn read_text(
+    mut reader: impl Read,
+    mut f: impl FnMut(SuumoElement) -> Result<(), String>,
+) -> Result<u64, String> {
+    let mut parser = SuumoElementParser::new();
+    let mut count = 0;
+    let mut buffer = vec![0; 1 << 16];
+    let mut start = 0;
+    loop {
+        let n = match reader.read(&mut buffer[start..]) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(format!("Failed to read transcript: {}", e)),
+        };
+        if n == 0 {
+            if start != 0 {
+                return Err("Invalid UTF-8 at the end of the transcript".to_string());
+            }
+            break;
+        }
+
+        let end = start + n;
+        let valid = match std::str::from_utf8(&buffer[..end]) {
+            Ok(s) => s.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Err("Invalid UTF-8 in the transcript".to_string()),
+        };
+        for c in std::str::from_utf8(&buffer[..valid]).unwrap().chars() {
+            if parser.is_empty() && (c == '\n' || c == '\r') {
+                continue;
+            }
+            if let Some(element) = parser
+                .push(c)
+                .map_err(|e| format!("{} (after {} elements)", e, count))?
+            {
+                count += 1;
+                f(element)?;
+            }
+        }
+        buffer.copy_within(valid..end, 0);
+        start = end - valid;
+    }
+    parser
+        .finish()
+        .map_err(|e| format!("{} (after {} elements)", e, count))?;
+
+    Ok(count)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    pub elements: u64,
+    pub state: SuumoState,
+}
+impl Verification {
+    pub fn is_terminated(&self) -> bool {
+        self.state == SuumoState::S19
+    }
+}
+
+// Checks that `reader` holds a text or binary transcript of a single run:
+// every element parses, a binary trailer matches its elements, and nothing
+// follows the jingle.
+pub fn verify(reader: impl Read) -> Result<Verification, String> {
+    let mut state = SuumoState::new();
+    let mut elements = 0;
+    read_elements(reader, |element| {
+        if state == SuumoState::S19 {
+            return Err(format!(
+                "Element {} follows the jingle, which ended at element {}",
+                elements + 1,
+                elements
+            ));
+        }
+        state.next_with_suumo_element(element);
+        elements += 1;
+        Ok(())
+    })?;
+
+    Ok(Verification { elements, state })
+}
+
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<(), String> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => "Unexpected end of the transcript".to_string(),
+        _ => format!("Failed to read transcript: {}", e),
+    })
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], String> {
+    let mut buf = [0; N];
+    read_exact(reader, &mut buf)?;
+
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JINGLE: &str = "あ❗️ スーモ❗️🌚ダン💥ダン💥ダン💥シャーン🎶スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚ス〜〜〜モ⤴🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝ス〜〜〜モ⤵🌞";
+
+    fn encode(elements: &[SuumoElement], seed: Option<u64>) -> Vec<u8> {
+        let mut encoder = TranscriptEncoder::new(Vec::new(), seed).unwrap();
+        for element in elements {
+            encoder.push(element).unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Vec<SuumoElement>, String> {
+        TranscriptDecoder::new(bytes)?.collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut elements = SuumoElement::ALL.to_vec();
+        elements.extend(crate::try_string_to_suumo_elements(JINGLE.to_string()).unwrap());
+        let bytes = encode(&elements, Some(19));
+
+        assert!(is_binary(&bytes));
+        let mut decoder = TranscriptDecoder::new(&bytes[..]).unwrap();
+        assert_eq!(decoder.seed(), Some(19));
+        let decoded: Result<Vec<_>, _> = decoder.by_ref().collect();
+        assert_eq!(decoded.unwrap(), elements);
+        assert_eq!(decoder.state(), SuumoState::S19);
+        assert_eq!(decoder.total(), 26);
+    }
+
+    #[test]
+    fn round_trip_multiple_blocks() {
+        let elements: Vec<_> = (0..BLOCK_SIZE * 2 + 5)
+            .map(|i| SuumoElement::ALL[i * 5 % 7].clone())
+            .collect();
+        let bytes = encode(&elements, None);
+
+        assert_eq!(decode(&bytes).unwrap(), elements);
+        assert!(bytes.len() < elements.len() * BITS / 8 + 200);
+    }
+
+    #[test]
+    fn empty_transcript() {
+        let bytes = encode(&[], None);
+        let mut decoder = TranscriptDecoder::new(&bytes[..]).unwrap();
+
+        assert_eq!(decoder.seed(), None);
+        assert_eq!(decoder.next_element(), Ok(None));
+        assert_eq!(decoder.state(), SuumoState::S0);
+    }
+
+    #[test]
+    fn packed_layout() {
+        let bytes = encode(
+            &[
+                SuumoElement::Dan,
+                SuumoElement::SuuuumoDown,
+                SuumoElement::Shaan,
+            ],
+            None,
+        );
+        let header_len = 4
+            + 2
+            + 1
+            + SuumoElement::ALL
+                .iter()
+                .map(|e| 1 + e.as_str().len())
+                .sum::<usize>();
+
+        assert_eq!(
+            &bytes[header_len..],
+            [
+                3,
+                0,
+                0,
+                0, // block of 3 elements
+                0b1011_0001,
+                0b0000_0000, // 1, 6, 2
+                0,
+                0,
+                0,
+                0, // end of the body
+                0, // S0
+                3,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_transcripts() {
+        let bytes = encode(&[SuumoElement::Dan, SuumoElement::Shaan], Some(1));
+
+        assert_eq!(
+            decode(JINGLE.as_bytes()).err(),
+            Some("Not a binary transcript".to_string())
+        );
+        assert_eq!(
+            decode(&bytes[..bytes.len() - 1]).err(),
+            Some("Unexpected end of the transcript".to_string())
+        );
+
+        let mut version = bytes.clone();
+        version[4] = 2;
+        assert_eq!(
+            decode(&version).err(),
+            Some("Unsupported transcript version: 2".to_string())
+        );
+
+        let mut count = bytes.clone();
+        let len = count.len();
+        count[len - 8] = 3;
+        assert!(decode(&count)
+            .unwrap_err()
+            .starts_with("Corrupted transcript"));
+
+        let mut index = bytes.clone();
+        index[len - 14] = 0b0011_1111;
+        assert_eq!(
+            decode(&index).err(),
+            Some("Invalid element index: 7".to_string())
+        );
+    }
+
+    #[test]
+    fn read_either_format() {
+        let elements = crate::try_string_to_suumo_elements(JINGLE.to_string()).unwrap();
+        let bytes = encode(&elements, None);
+
+        for input in [&bytes[..], JINGLE.as_bytes()] {
+            let mut decoded = Vec::new();
+            let count = read_elements(input, |element| {
+                decoded.push(element);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(count, 19);
+            assert_eq!(decoded, elements);
+        }
+        assert_eq!(read_elements(&b""[..], |_| Ok(())), Ok(0));
+        assert_eq!(
+            read_elements(&b"SU"[..], |_| Ok(())).unwrap_err(),
+            "Invalid string: S (after 0 elements)"
+        );
+    }
+
+    #[test]
+    fn text_transcript() {
+        let mut elements = Vec::new();
+        let count = read_text(format!("ダン💥\r\n{}\n", JINGLE).as_bytes(), |element| {
+            elements.push(element);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(count, 20);
+        assert_eq!(elements[0], SuumoElement::Dan);
+        assert_eq!(
+            read_text("ダン💥x".as_bytes(), |_| Ok(())),
+            Err("Invalid string: x (after 1 elements)".to_string())
+        );
+        assert_eq!(
+            read_text("ダン💥".as_bytes(), |_| Err("stop".to_string())),
+            Err("stop".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_transcript() {
+        let verification = verify(format!("ダン💥{}\n", JINGLE).as_bytes()).unwrap();
+        assert_eq!(verification.elements, 20);
+        assert!(verification.is_terminated());
+
+        let verification = verify(encode(&SuumoElement::JINGLE[..5], None).as_slice()).unwrap();
+        assert_eq!(verification.elements, 5);
+        assert_eq!(verification.state, SuumoState::S5);
+        assert!(!verification.is_terminated());
+
+        assert_eq!(
+            verify(format!("{}ダン💥", JINGLE).as_bytes()),
+            Err("Element 20 follows the jingle, which ended at element 19".to_string())
+        );
+        assert!(verify("ダン".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn encoder_observer() {
+        use crate::{generator::ElementGenerator, observer::run_observed};
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut encoder = TranscriptEncoder::new(Vec::new(), Some(19)).unwrap();
+        let mut state = SuumoState::new();
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        run_observed(&mut state, &mut generator, Some(1000), &mut encoder).unwrap();
+        let bytes = encoder.finish().unwrap();
+
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        let expected: Vec<SuumoElement> = generator.by_ref().take(1000).collect();
+        let mut decoder = TranscriptDecoder::new(bytes.as_slice()).unwrap();
+        assert_eq!(decoder.seed(), Some(19));
+        assert_eq!(
+            decoder.by_ref().collect::<Result<Vec<_>, _>>(),
+            Ok(expected)
+        );
+        assert_eq!(decoder.state(), state);
+    }
+}
@@ -0,0 +1,197 @@
+// JavaScript API for the `wasm` feature. Build it with
+// `wasm-pack build suumo-gen-wasm --target web`.
+
+use rand::{rngs::StdRng, SeedableRng};
+use wasm_bindgen::prelude::*;
+
+use crate::{generator::ElementGenerator, try_string_to_suumo_elements, SuumoElement, SuumoState};
+
+/// The jingle state machine. `index` is 19 once the whole jingle has been fed.
+#[wasm_bindgen(js_name = SuumoState)]
+#[derive(Debug, Clone, Default)]
+pub struct WasmSuumoState {
+    state: SuumoState,
+}
+#[wasm_bindgen(js_class = SuumoState)]
+impl WasmSuumoState {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> u32 {
+        self.state.index() as u32
+    }
+
+    #[wasm_bindgen(getter, js_name = isTerminal)]
+    pub fn is_terminal(&self) -> bool {
+        self.state == SuumoState::S19
+    }
+
+    /// Feeds the element with index `element` and returns the index of the
+    /// new state. Throws if `element` is out of range.
+    pub fn feed(&mut self, element: u32) -> Result<u32, JsError> {
+        self.try_feed(element).map_err(|e| JsError::new(&e))
+    }
+
+    pub fn reset(&mut self) {
+        self.state = SuumoState::new();
+    }
+
+    fn try_feed(&mut self, element: u32) -> Result<u32, String> {
+        if element as usize >= SuumoElement::ALL.len() {
+            return Err(format!("Invalid element: {}", element));
+        }
+        let next = SuumoState::TRANSITIONS[self.state.index()][element as usize];
+        self.state = SuumoState::ALL[next as usize].clone();
+
+        Ok(next as u32)
+    }
+}
+
+/// Seeded element generator. The same seed always gives the same run.
+#[wasm_bindgen(js_name = Generator)]
+#[derive(Debug, Clone)]
+pub struct WasmGenerator {
+    generator: ElementGenerator<StdRng>,
+    state: SuumoState,
+    steps: u64,
+}
+#[wasm_bindgen(js_class = Generator)]
+impl WasmGenerator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u64) -> Self {
+        Self {
+            generator: ElementGenerator::new(StdRng::seed_from_u64(seed)),
+            state: SuumoState::new(),
+            steps: 0,
+        }
+    }
+
+    /// Generates the next element and returns its index, or `undefined` once
+    /// the jingle has been generated.
+    #[wasm_bindgen(js_name = next)]
+    pub fn next_index(&mut self) -> Option<u32> {
+        if self.state == SuumoState::S19 {
+            return None;
+        }
+        let element = self.generator.next_index();
+        let next = SuumoState::TRANSITIONS[self.state.index()][element];
+        self.state = SuumoState::ALL[next as usize].clone();
+        self.steps += 1;
+
+        Some(element as u32)
+    }
+
+    /// Generates up to `limit` elements and returns their text. Returns less
+    /// once the jingle has been generated.
+    pub fn text(&mut self, limit: u32) -> String {
+        let mut text = String::new();
+        for _ in 0..limit {
+            let Some(element) = self.next_index() else {
+                break;
+            };
+            text.push_str(SuumoElement::ALL[element as usize].as_str());
+        }
+
+        text
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn state(&self) -> u32 {
+        self.state.index() as u32
+    }
+
+    // A number rather than a BigInt, which is exact up to 2^53 steps.
+    #[wasm_bindgen(getter)]
+    pub fn steps(&self) -> f64 {
+        self.steps as f64
+    }
+
+    #[wasm_bindgen(getter, js_name = isTerminal)]
+    pub fn is_terminal(&self) -> bool {
+        self.state == SuumoState::S19
+    }
+}
+
+/// Parses a string into element indices. Throws if it is not a sequence of
+/// elements.
+#[wasm_bindgen]
+pub fn parse(s: &str) -> Result<Vec<u8>, JsError> {
+    try_parse(s).map_err(|e| JsError::new(&e))
+}
+
+/// Returns the text of the element with index `element`.
+#[wasm_bindgen(js_name = elementText)]
+pub fn element_text(element: u32) -> Option<String> {
+    SuumoElement::ALL
+        .get(element as usize)
+        .map(|element| element.as_str().to_string())
+}
+
+fn try_parse(s: &str) -> Result<Vec<u8>, String> {
+    Ok(try_string_to_suumo_elements(s.to_string())?
+        .iter()
+        .map(|element| element.index() as u8)
+        .collect())
+}
+
+// `JsError` needs a JavaScript host, so only the error-free paths of the
+// exported functions are called here. suumo-gen-wasm/tests/wasm.rs covers the
+// rest under `wasm-pack test --node`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wasm_state_machine() {
+        let mut state = WasmSuumoState::new();
+        assert_eq!(state.index(), 0);
+        for (i, element) in SuumoElement::JINGLE.iter().enumerate() {
+            assert_eq!(state.feed(element.index() as u32).ok(), Some(i as u32 + 1));
+        }
+        assert!(state.is_terminal());
+        assert_eq!(state.try_feed(7), Err("Invalid element: 7".to_string()));
+
+        state.reset();
+        assert_eq!(state.index(), 0);
+        assert!(!state.is_terminal());
+    }
+
+    #[test]
+    fn wasm_generator_is_seeded() {
+        let mut a = WasmGenerator::new(19);
+        let mut b = WasmGenerator::new(19);
+        let text = a.text(1000);
+        assert_eq!(text, b.text(1000));
+        assert_eq!(a.steps(), 1000.0);
+        assert_eq!(a.state(), b.state());
+
+        let mut generator = ElementGenerator::new(StdRng::seed_from_u64(19));
+        let expected: String = (0..1000)
+            .map(|_| SuumoElement::ALL[generator.next_index()].as_str())
+            .collect();
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn wasm_generator_stops_at_jingle() {
+        let mut generator = WasmGenerator::new(19);
+        generator.state = SuumoState::S18;
+        while !generator.is_terminal() {
+            generator.state = SuumoState::S18;
+            generator.next_index();
+        }
+        assert_eq!(generator.next_index(), None);
+        assert_eq!(generator.text(10), "");
+    }
+
+    #[test]
+    fn wasm_parse() {
+        assert_eq!(parse("ダン💥シャーン🎶スモ🌝").ok(), Some(vec![1, 2, 3]));
+        assert_eq!(element_text(1), Some("ダン💥".to_string()));
+        assert_eq!(element_text(7), None);
+        assert!(try_parse("ダン").is_err());
+    }
+}
@@ -0,0 +1,45 @@
+use rand::RngCore;
+
+use crate::SuumoElement;
+
+// Draws elements 3 bits at a time from 64-bit random words instead of asking
+// the RNG for every element. A 3-bit value of 7 is rejected, so the accepted
+// values are uniform over the 7 elements.
+#[derive(Debug, Clone)]
+pub struct ElementGenerator<R> {
+    rng: R,
+    bits: u64,
+    remaining: u32,
+}
+impl<R: RngCore> ElementGenerator<R> {
+    pub fn new(rng: R) -> Self {
+        Self {
+            rng,
+            bits: 0,
+            remaining: 0,
+        }
+    }
+
+    #[inline]
+    pub fn next_index(&mut self) -> usize {
+        loop {
+            if self.remaining == 0 {
+                self.bits = self.rng.next_u64();
+                self.remaining = 21;
+            }
+            let value = (self.bits & 0b111) as usize;
+            self.bits >>= 3;
+            self.remaining -= 1;
+            if value < SuumoElement::ALL.len() {
+                return value;
+            }
+        }
+    }
+}
+impl<R: RngCore> Iterator for ElementGenerator<R> {
+    type Item = SuumoElement;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(SuumoElement::ALL[self.next_index()].clone())
+    }
+}
@@ -0,0 +1,471 @@
+// The state machine of the jingle, usable without `std`. With the `alloc`
+// feature it also parses and collects strings; with `std` (the default) it
+// can draw elements from `rand::thread_rng`.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Display;
+
+use rand::{Rng, RngCore};
+
+pub mod generator;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SuumoElement {
+    ASuumo,
+    Dan,
+    Shaan,
+    SumoFullMoon,
+    SumoNewMoon,
+    SuuuumoUp,
+    SuuuumoDown,
+}
+impl SuumoElement {
+    pub const ALL: [SuumoElement; 7] = [
+        Self::ASuumo,
+        Self::Dan,
+        Self::Shaan,
+        Self::SumoFullMoon,
+        Self::SumoNewMoon,
+        Self::SuuuumoUp,
+        Self::SuuuumoDown,
+    ];
+    pub const JINGLE: [SuumoElement; 19] = [
+        Self::ASuumo,
+        Self::Dan,
+        Self::Dan,
+        Self::Dan,
+        Self::Shaan,
+        Self::SumoFullMoon,
+        Self::SumoNewMoon,
+        Self::SumoFullMoon,
+        Self::SumoNewMoon,
+        Self::SumoFullMoon,
+        Self::SumoNewMoon,
+        Self::SuuuumoUp,
+        Self::SumoNewMoon,
+        Self::SumoFullMoon,
+        Self::SumoNewMoon,
+        Self::SumoFullMoon,
+        Self::SumoNewMoon,
+        Self::SumoFullMoon,
+        Self::SuuuumoDown,
+    ];
+
+    #[cfg(feature = "std")]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self::new_with_rng(&mut rand::thread_rng())
+    }
+
+    pub fn new_with_rng<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        match rng.gen_range(0..7) {
+            0 => Self::ASuumo,
+            1 => Self::Dan,
+            2 => Self::Shaan,
+            3 => Self::SumoFullMoon,
+            4 => Self::SumoNewMoon,
+            5 => Self::SuuuumoUp,
+            6 => Self::SuuuumoDown,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ASuumo => "あ❗️ スーモ❗️🌚",
+            Self::Dan => "ダン💥",
+            Self::Shaan => "シャーン🎶",
+            Self::SumoFullMoon => "スモ🌝",
+            Self::SumoNewMoon => "スモ🌚",
+            Self::SuuuumoUp => "ス〜〜〜モ⤴🌝",
+            Self::SuuuumoDown => "ス〜〜〜モ⤵🌞",
+        }
+    }
+
+    pub const fn index(&self) -> usize {
+        match self {
+            Self::ASuumo => 0,
+            Self::Dan => 1,
+            Self::Shaan => 2,
+            Self::SumoFullMoon => 3,
+            Self::SumoNewMoon => 4,
+            Self::SuuuumoUp => 5,
+            Self::SuuuumoDown => 6,
+        }
+    }
+}
+impl Display for SuumoElement {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+#[cfg(feature = "alloc")]
+impl From<SuumoElement> for String {
+    fn from(s: SuumoElement) -> String {
+        s.to_string()
+    }
+}
+#[cfg(feature = "alloc")]
+impl TryFrom<String> for SuumoElement {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<SuumoElement, String> {
+        match s.as_str() {
+            "あ❗️ スーモ❗️🌚" => Ok(Self::ASuumo),
+            "ダン💥" => Ok(Self::Dan),
+            "シャーン🎶" => Ok(Self::Shaan),
+            "スモ🌝" => Ok(Self::SumoFullMoon),
+            "スモ🌚" => Ok(Self::SumoNewMoon),
+            "ス〜〜〜モ⤴🌝" => Ok(Self::SuuuumoUp),
+            "ス〜〜〜モ⤵🌞" => Ok(Self::SuuuumoDown),
+            _ => Err(format!("Invalid string: {}", s)),
+        }
+    }
+}
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SuumoElementParser {
+    buffer: String,
+    len: usize,
+}
+#[cfg(feature = "alloc")]
+impl SuumoElementParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, c: char) -> Result<Option<SuumoElement>, String> {
+        self.buffer.push(c);
+        self.len += 1;
+
+        let mut chars = self.buffer.chars();
+        let len = match (chars.next(), chars.next()) {
+            (Some('あ'), _) => 10,
+            (Some('ダ'), _) => 3,
+            (Some('シ'), _) => 5,
+            (Some('ス'), None) => return Ok(None),
+            (Some('ス'), Some('〜')) => 7,
+            (Some('ス'), Some('モ')) => 3,
+            _ => return Err(self.invalid()),
+        };
+        if self.len < len {
+            return Ok(None);
+        }
+
+        self.len = 0;
+        SuumoElement::try_from(core::mem::take(&mut self.buffer)).map(Some)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn finish(&mut self) -> Result<(), String> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self.invalid())
+        }
+    }
+
+    fn invalid(&mut self) -> String {
+        self.len = 0;
+        format!("Invalid string: {}", core::mem::take(&mut self.buffer))
+    }
+}
+#[cfg(feature = "alloc")]
+pub fn try_string_to_suumo_elements(value: String) -> Result<Vec<SuumoElement>, String> {
+    let mut result = Vec::new();
+    let mut parser = SuumoElementParser::new();
+    for c in value.chars() {
+        if let Some(element) = parser
+            .push(c)
+            .map_err(|_| format!("Invalid string: {}", value))?
+        {
+            result.push(element);
+        }
+    }
+    parser
+        .finish()
+        .map_err(|_| format!("Invalid string: {}", value))?;
+
+    Ok(result)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SuumoState {
+    S0,
+    S1,
+    S2,
+    S3,
+    S4,
+    S5,
+    S6,
+    S7,
+    S8,
+    S9,
+    S10,
+    S11,
+    S12,
+    S13,
+    S14,
+    S15,
+    S16,
+    S17,
+    S18,
+    S19,
+}
+impl SuumoState {
+    pub const ALL: [SuumoState; 20] = [
+        Self::S0,
+        Self::S1,
+        Self::S2,
+        Self::S3,
+        Self::S4,
+        Self::S5,
+        Self::S6,
+        Self::S7,
+        Self::S8,
+        Self::S9,
+        Self::S10,
+        Self::S11,
+        Self::S12,
+        Self::S13,
+        Self::S14,
+        Self::S15,
+        Self::S16,
+        Self::S17,
+        Self::S18,
+        Self::S19,
+    ];
+    // `TRANSITIONS[state][element]` is the index of the state reached from
+    // `SuumoState::ALL[state]` on `SuumoElement::ALL[element]`. `S19` only
+    // transitions to itself.
+    pub const TRANSITIONS: [[u8; 7]; 20] = build_transitions();
+
+    pub fn new() -> Self {
+        Self::S0
+    }
+
+    pub const fn index(&self) -> usize {
+        match self {
+            Self::S0 => 0,
+            Self::S1 => 1,
+            Self::S2 => 2,
+            Self::S3 => 3,
+            Self::S4 => 4,
+            Self::S5 => 5,
+            Self::S6 => 6,
+            Self::S7 => 7,
+            Self::S8 => 8,
+            Self::S9 => 9,
+            Self::S10 => 10,
+            Self::S11 => 11,
+            Self::S12 => 12,
+            Self::S13 => 13,
+            Self::S14 => 14,
+            Self::S15 => 15,
+            Self::S16 => 16,
+            Self::S17 => 17,
+            Self::S18 => 18,
+            Self::S19 => 19,
+        }
+    }
+
+    pub fn next_with_suumo_element(&mut self, next_element: SuumoElement) -> Option<Self> {
+        if *self == Self::S19 {
+            return None;
+        }
+        *self = Self::ALL[Self::TRANSITIONS[self.index()][next_element.index()] as usize].clone();
+
+        Some(self.clone())
+    }
+
+    // Draws the next element from `rng` and moves to the next state. Returns
+    // `None` once the jingle has been generated.
+    pub fn next_with_rng<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<(Self, SuumoElement)> {
+        let next_element = SuumoElement::new_with_rng(rng);
+        self.next_with_suumo_element(next_element.clone())
+            .map(|state| (state, next_element))
+    }
+
+    pub fn run_until_terminal<R: RngCore + ?Sized>(&mut self, rng: &mut R) -> u64 {
+        self.run_until_terminal_nth(rng, u64::MAX)
+    }
+
+    pub fn run_until_terminal_nth<R: RngCore + ?Sized>(&mut self, rng: &mut R, n: u64) -> u64 {
+        let mut generator = generator::ElementGenerator::new(rng);
+        let mut current = self.index() as u8;
+        let mut count = 0;
+        while current != Self::S19.index() as u8 && count < n {
+            current = Self::TRANSITIONS[current as usize][generator.next_index()];
+            count += 1;
+        }
+        *self = Self::ALL[current as usize].clone();
+
+        count
+    }
+
+    #[cfg(feature = "std")]
+    pub fn collect_to_string(&mut self) -> String {
+        let mut result = String::new();
+        for (_, element) in self {
+            result.push_str(&element.to_string());
+        }
+
+        result
+    }
+
+    #[cfg(feature = "std")]
+    pub fn collect_to_string_nth(&mut self, n: usize) -> String {
+        let mut result = String::new();
+        for _ in 0..n {
+            if let Some((_, element)) = self.next() {
+                result.push_str(&element.to_string());
+            } else {
+                break;
+            }
+        }
+
+        result
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn collect_to_string_with_suumo_elements(
+        &mut self,
+        elements: impl IntoIterator<Item = SuumoElement>,
+    ) -> String {
+        let mut result = String::new();
+        for element in elements {
+            if self.next_with_suumo_element(element.clone()).is_some() {
+                result.push_str(&element.to_string());
+            } else {
+                break;
+            }
+        }
+
+        result
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn collect_to_string_with_suumo_elements_nth(
+        &mut self,
+        n: usize,
+        elements: impl IntoIterator<Item = SuumoElement>,
+    ) -> String {
+        let mut result = String::new();
+        for (i, element) in elements.into_iter().enumerate() {
+            if i < n {
+                if self.next_with_suumo_element(element.clone()).is_some() {
+                    result.push_str(&element.to_string());
+                } else {
+                    break;
+                }
+            } else {
+                break;
+            }
+        }
+
+        result
+    }
+}
+// Builds the KMP automaton of `SuumoElement::JINGLE`: state `n` means the last
+// `n` elements match the first `n` elements of the jingle.
+const fn build_transitions() -> [[u8; 7]; 20] {
+    let jingle = SuumoElement::JINGLE;
+    let mut table = [[0; 7]; 20];
+
+    // `prefix[i]` is the length of the longest proper prefix of `jingle[..=i]`
+    // that is also its suffix.
+    let mut prefix = [0; 19];
+    let mut i = 1;
+    while i < jingle.len() {
+        let mut len = prefix[i - 1];
+        while len > 0 && jingle[i].index() != jingle[len].index() {
+            len = prefix[len - 1];
+        }
+        if jingle[i].index() == jingle[len].index() {
+            len += 1;
+        }
+        prefix[i] = len;
+        i += 1;
+    }
+
+    let mut state = 0;
+    while state < jingle.len() {
+        let mut element = 0;
+        while element < SuumoElement::ALL.len() {
+            table[state][element] = if jingle[state].index() == element {
+                state as u8 + 1
+            } else if state == 0 {
+                0
+            } else {
+                table[prefix[state - 1]][element]
+            };
+            element += 1;
+        }
+        state += 1;
+    }
+    table[jingle.len()] = [jingle.len() as u8; 7];
+
+    table
+}
+impl Default for SuumoState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+#[cfg(feature = "std")]
+impl Iterator for SuumoState {
+    type Item = (SuumoState, SuumoElement);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_rng(&mut rand::thread_rng())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+
+    // Every test here only uses what is available without `std`.
+    #[test]
+    fn next_with_rng() {
+        let mut state = SuumoState::new();
+        let mut rng = StepRng::new(0, 0);
+        let element = SuumoElement::new_with_rng(&mut rng.clone());
+
+        assert_eq!(
+            state.next_with_rng(&mut rng),
+            Some((SuumoState::S1, element.clone()))
+        );
+        assert_eq!(element, SuumoElement::ASuumo);
+
+        let mut state = SuumoState::S19;
+        assert_eq!(state.next_with_rng(&mut rng), None);
+    }
+
+    #[test]
+    fn run_until_terminal_with_explicit_rng() {
+        let word = SuumoElement::JINGLE
+            .iter()
+            .enumerate()
+            .fold(0, |word, (i, element)| {
+                word | (element.index() as u64) << (3 * i)
+            });
+        let mut state = SuumoState::new();
+
+        assert_eq!(state.run_until_terminal(&mut StepRng::new(word, 0)), 19);
+        assert_eq!(state, SuumoState::S19);
+    }
+}
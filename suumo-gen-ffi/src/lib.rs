@@ -0,0 +1,4 @@
+// Links the C API of `suumo_gen::ffi` into `libsuumo_gen.a` and
+// `libsuumo_gen.so`. The header is `include/suumo_gen.h` of the main crate.
+
+pub use suumo_gen::ffi::*;
@@ -0,0 +1,40 @@
+use std::{env, path::PathBuf, process::Command};
+
+// Compiles `tests/ffi/test.c` against `include/suumo_gen.h` of the main crate
+// and the static library, and runs it.
+#[test]
+fn c_test_program() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let include_dir = manifest_dir.join("../include");
+    // `cargo test` builds `libsuumo_gen.a` into `target/<profile>/deps`,
+    // next to this test binary.
+    let exe = env::current_exe().unwrap();
+    let deps_dir = exe.parent().unwrap();
+    let output = deps_dir.join("ffi_test");
+
+    let status = Command::new(env::var("CC").unwrap_or("cc".to_string()))
+        .arg(manifest_dir.join("tests/ffi/test.c"))
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(include_dir)
+        .arg(deps_dir.join("libsuumo_gen.a"))
+        .args(["-lpthread", "-ldl", "-lm"])
+        // Drops code the program never calls, such as the Python module when
+        // the `python` feature is on, so it does not need libpython.
+        .arg("-Wl,--gc-sections")
+        .arg("-o")
+        .arg(&output)
+        .status()
+        .expect("Failed to run the C compiler");
+    assert!(status.success());
+
+    let result = Command::new(&output).output().unwrap();
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert_eq!(result.stdout, b"ok\n");
+}
@@ -0,0 +1,52 @@
+#include <stdio.h>
+#include <string.h>
+
+#include "suumo_gen.h"
+
+#define CHECK(cond)                                                      \
+    do {                                                                 \
+        if (!(cond)) {                                                   \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,       \
+                    __LINE__, #cond);                                    \
+            return 1;                                                    \
+        }                                                                \
+    } while (0)
+
+static const char *JINGLE =
+    "あ❗️ スーモ❗️🌚ダン💥ダン💥ダン💥シャーン🎶スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚"
+    "ス〜〜〜モ⤴🌝スモ🌚スモ🌝スモ🌚スモ🌝スモ🌚スモ🌝ス〜〜〜モ⤵🌞";
+
+int main(void) {
+    uint8_t elements[32];
+    char rendered[1024] = "";
+    char buf[64];
+
+    ptrdiff_t count = suumo_parse(JINGLE, elements, sizeof(elements));
+    CHECK(count == 19);
+    CHECK(suumo_parse("ダン💥x", elements, sizeof(elements)) == -1);
+
+    struct SuumoGenState *state = suumo_state_new();
+    CHECK(state != NULL);
+    CHECK(suumo_state_index(state) == 0);
+    for (ptrdiff_t i = 0; i < count; i++) {
+        CHECK(suumo_state_feed(state, elements[i]) == i + 1);
+
+        ptrdiff_t len = suumo_element_render(elements[i], buf, sizeof(buf));
+        CHECK(len > 0 && (size_t)len == strlen(buf));
+        strcat(rendered, buf);
+    }
+    CHECK(suumo_state_index(state) == SUUMO_TERMINAL_STATE);
+    CHECK(strcmp(rendered, JINGLE) == 0);
+
+    CHECK(suumo_state_feed(state, SUUMO_ELEMENT_COUNT) == -1);
+    suumo_state_reset(state);
+    CHECK(suumo_state_index(state) == 0);
+    CHECK(suumo_state_feed(state, 1) == 0);
+    suumo_state_free(state);
+
+    CHECK(suumo_element_render(SUUMO_ELEMENT_COUNT, buf, sizeof(buf)) == -1);
+    CHECK(suumo_state_index(NULL) == -1);
+
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,4 @@
+// Links the JavaScript API of `suumo_gen::wasm` into a module for wasm-pack:
+// `wasm-pack build suumo-gen-wasm --target web`.
+
+pub use suumo_gen::wasm::*;
@@ -0,0 +1,31 @@
+// Runs in Node.js: `wasm-pack test suumo-gen-wasm --node`.
+#![cfg(target_arch = "wasm32")]
+
+use suumo_gen_core::SuumoElement;
+use suumo_gen_wasm::{element_text, parse, WasmGenerator, WasmSuumoState};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn state_machine() {
+    let mut state = WasmSuumoState::new();
+    for element in SuumoElement::JINGLE.iter() {
+        assert!(state.feed(element.index() as u32).is_ok());
+    }
+    assert!(state.is_terminal());
+    assert!(state.feed(7).is_err());
+}
+
+#[wasm_bindgen_test]
+fn generator() {
+    let mut a = WasmGenerator::new(19);
+    let mut b = WasmGenerator::new(19);
+    assert_eq!(a.text(1000), b.text(1000));
+    assert_eq!(a.steps(), 1000.0);
+}
+
+#[wasm_bindgen_test]
+fn parser() {
+    assert_eq!(parse("ダン💥シャーン🎶").ok(), Some(vec![1, 2]));
+    assert!(parse("ダン").is_err());
+    assert_eq!(element_text(2).as_deref(), Some("シャーン🎶"));
+}
@@ -0,0 +1,185 @@
+// Runs the binary to check the exit statuses listed in its help text, the
+// summary line it prints on stderr and how it applies suumo-gen.toml.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+// Runs in an empty directory with an empty config directory and without the
+// SUUMO_GEN_* variables, so that no suumo-gen.toml or environment of the
+// machine running the tests changes the defaults.
+fn suumo_gen(name: &str) -> (Command, PathBuf) {
+    let dir = env::temp_dir().join(format!("suumo-gen-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    (command_in(&dir), dir)
+}
+
+fn command_in(dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_suumo-gen"));
+    command
+        .current_dir(dir)
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .stdin(Stdio::null());
+    for (key, _) in env::vars_os() {
+        if key.to_string_lossy().starts_with("SUUMO_GEN_") {
+            command.env_remove(key);
+        }
+    }
+    command
+}
+
+fn summary(output: &Output) -> String {
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    stderr.lines().last().unwrap_or_default().to_string()
+}
+
+#[test]
+fn terminated() {
+    let (mut command, dir) = suumo_gen("terminated");
+    fs::write(
+        dir.join("pattern.toml"),
+        "[[elements]]\nname = \"Dan\"\ntext = \"ダン💥\"\n\n[[targets]]\nsequence = [\"Dan\"]\n",
+    )
+    .unwrap();
+
+    let output = command
+        .args(["--pattern-file", "pattern.toml"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, "ダン💥".as_bytes());
+    assert!(
+        summary(&output).starts_with("terminated: reached S1 after 1 elements ("),
+        "{}",
+        summary(&output)
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn limit_hit() {
+    let (mut command, dir) = suumo_gen("limit");
+    command.args(["--limit", "1", "--seed", "19"]);
+    if cfg!(feature = "multi-thread") {
+        command.args(["--threads", "1"]);
+    }
+    let output = command.output().unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.ends_with(b"\n"));
+    assert!(
+        summary(&output).starts_with("limit hit: stopped in S")
+            && summary(&output).contains(" after 1 elements, deepest S"),
+        "{}",
+        summary(&output)
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn timeout() {
+    let (mut command, dir) = suumo_gen("timeout");
+    let output = command
+        .args(["--timeout", "100ms", "--count-only"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        summary(&output).starts_with("timeout: stopped in S"),
+        "{}",
+        summary(&output)
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn error() {
+    let (mut command, dir) = suumo_gen("error");
+    let output = command.args(["--limit", "x"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    fs::remove_dir_all(dir).unwrap();
+
+    let (mut command, dir) = suumo_gen("missing-pattern");
+    let output = command
+        .args(["--pattern-file", "missing.toml"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn interrupted() {
+    use std::{thread, time::Duration};
+
+    let (mut command, dir) = suumo_gen("interrupted");
+    let child = command
+        .arg("--count-only")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Gives the binary time to install its Ctrl-C handler.
+    thread::sleep(Duration::from_millis(500));
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert!(
+        summary(&output).starts_with("interrupted: stopped in S"),
+        "{}",
+        summary(&output)
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn config_output_is_only_for_run() {
+    let (_, dir) = suumo_gen("config-output");
+    fs::write(dir.join("suumo-gen.toml"), "output = \"out.txt\"\n").unwrap();
+
+    for args in [&["diagram"][..], &["completions", "bash"]] {
+        let output = command_in(&dir).args(args).output().unwrap();
+        assert_eq!(output.status.code(), Some(0), "{:?}", args);
+        assert!(!output.stdout.is_empty(), "{:?}", args);
+        assert!(!dir.join("out.txt").exists(), "{:?}", args);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn config_rendering() {
+    let (mut command, dir) = suumo_gen("config-rendering");
+    fs::write(dir.join("suumo-gen.toml"), "rendering = \"count\"\n").unwrap();
+
+    let output = command.args(["--limit", "5"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(output.stdout, b"5\n");
+
+    let output = command_in(&dir)
+        .args(["--limit", "5", "--progress"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+
+    let output = command_in(&dir)
+        .args(["--limit", "1", "--rendering", "elements"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_ne!(output.stdout, b"1\n");
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,18 @@
+#![cfg(feature = "ffi")]
+
+use std::{fs, path::PathBuf};
+
+// build.rs only writes the header to `OUT_DIR`, so that building never
+// modifies the package.
+#[test]
+fn header_is_up_to_date() {
+    let generated = PathBuf::from(env!("OUT_DIR")).join("suumo_gen.h");
+    let committed = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("include/suumo_gen.h");
+
+    assert!(
+        fs::read_to_string(&generated).unwrap() == fs::read_to_string(&committed).unwrap(),
+        "include/suumo_gen.h is out of date, update it with\n  cp {} {}",
+        generated.display(),
+        committed.display()
+    );
+}